@@ -0,0 +1,119 @@
+use crate::{
+    error::{Diagnostic, ReadError},
+    options::ParseOptions,
+    reader::parse_file_with_options,
+    Beatmap,
+};
+use rayon::prelude::*;
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// The result of parsing one file of a songs directory.
+#[derive(Debug)]
+pub struct ParsedFile {
+    pub path: PathBuf,
+    pub result: Result<Beatmap, ReadError>,
+    /// Empty unless the file was parsed in [`ParseMode::Lenient`](crate::ParseMode::Lenient).
+    pub diagnostics: Vec<Diagnostic>,
+    /// The time taken to read and parse the file.
+    pub elapsed: Duration,
+}
+
+/// Finds every `.osu` file below `path` and parses them in parallel. Symbolic links are followed,
+/// but each directory is only searched once.
+pub fn parse_songs_directory(path: impl AsRef<Path>) -> io::Result<Vec<ParsedFile>> {
+    parse_songs_directory_with_options(path, &ParseOptions::default())
+}
+
+/// Like [`parse_songs_directory`], with the same options for every file.
+///
+/// Only failing to read `path` itself is an error: a subdirectory that can't be read is reported
+/// as a [`ParsedFile`] with its path and the I/O error, after the beatmaps, which are sorted by
+/// path.
+pub fn parse_songs_directory_with_options(
+    path: impl AsRef<Path>,
+    options: &ParseOptions,
+) -> io::Result<Vec<ParsedFile>> {
+    let mut files = Vec::new();
+    let mut unreadable = Vec::new();
+    let mut visited = HashSet::from([fs::canonicalize(&path)?]);
+    for entry in fs::read_dir(path)? {
+        find_beatmaps(
+            entry.map(|t| t.path()),
+            &mut visited,
+            &mut files,
+            &mut unreadable,
+        );
+    }
+    files.sort();
+
+    let mut parsed: Vec<ParsedFile> = files
+        .into_par_iter()
+        .map(|path| {
+            let start = Instant::now();
+            let (result, diagnostics) = match parse_file_with_options(&path, options) {
+                Ok((beatmap, diagnostics)) => (Ok(beatmap), diagnostics),
+                Err(e) => (Err(e), Vec::new()),
+            };
+
+            ParsedFile {
+                path,
+                result,
+                diagnostics,
+                elapsed: start.elapsed(),
+            }
+        })
+        .collect();
+    parsed.extend(unreadable);
+
+    Ok(parsed)
+}
+
+/// `visited` holds the canonical paths of the directories already searched, so that a symbolic
+/// link back to a parent doesn't recurse forever.
+fn find_beatmaps(
+    path: io::Result<PathBuf>,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    unreadable: &mut Vec<ParsedFile>,
+) {
+    let path = match path {
+        Ok(t) => t,
+        Err(e) => return unreadable.push(unreadable_file(PathBuf::new(), e)),
+    };
+
+    if path.is_dir() {
+        let entries =
+            fs::canonicalize(&path).and_then(|canonical| match visited.insert(canonical) {
+                true => fs::read_dir(&path).map(Some),
+                false => Ok(None),
+            });
+        match entries {
+            Ok(Some(entries)) => {
+                for entry in entries {
+                    find_beatmaps(entry.map(|t| t.path()), visited, files, unreadable);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => unreadable.push(unreadable_file(path, e)),
+        }
+    } else if path
+        .extension()
+        .is_some_and(|t| t.eq_ignore_ascii_case("osu"))
+    {
+        files.push(path);
+    }
+}
+
+fn unreadable_file(path: PathBuf, error: io::Error) -> ParsedFile {
+    ParsedFile {
+        path,
+        result: Err(ReadError::Io(error)),
+        diagnostics: Vec::new(),
+        elapsed: Duration::ZERO,
+    }
+}
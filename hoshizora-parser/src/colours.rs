@@ -0,0 +1,230 @@
+use crate::{
+    error::{split_key_value, ParseError},
+    file_sections::FileSections,
+    Beatmap,
+};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+use strum::ParseError::VariantNotFound;
+
+/// Combo colours used by the default skin when a beatmap doesn't define its own.
+pub const DEFAULT_COMBO_COLOURS: [Colour; 4] = [
+    Colour::new(255, 192, 0),
+    Colour::new(0, 202, 0),
+    Colour::new(18, 124, 255),
+    Colour::new(242, 24, 57),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Colour {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: Option<u8>,
+}
+
+impl Colour {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha: None,
+        }
+    }
+
+    pub const fn with_alpha(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        Self {
+            red,
+            green,
+            blue,
+            alpha: Some(alpha),
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|t| t.trim().parse::<u8>().map_err(|_| VariantNotFound))
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        match values[..] {
+            [red, green, blue] => Ok(Self::new(red, green, blue)),
+            [red, green, blue, alpha] => Ok(Self::with_alpha(red, green, blue, alpha)),
+            _ => Err(VariantNotFound),
+        }
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.red, self.green, self.blue)?;
+        match self.alpha {
+            Some(alpha) => write!(f, ",{}", alpha),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Colours {
+    /// Colours defined by `ComboN` keys, ordered by `N`.
+    pub combo_colours: BTreeMap<u32, Colour>,
+    pub slider_track_override: Option<Colour>,
+    pub slider_border: Option<Colour>,
+    /// Keys this crate doesn't know, in file order, written back after the known ones.
+    pub extras: Vec<(String, String)>,
+}
+
+impl Colours {
+    pub fn new(
+        combo_colours: BTreeMap<u32, Colour>,
+        slider_track_override: Option<Colour>,
+        slider_border: Option<Colour>,
+    ) -> Self {
+        Self {
+            combo_colours,
+            slider_track_override,
+            slider_border,
+            extras: Vec::new(),
+        }
+    }
+
+    /// The combo colours objects cycle through: the ones defined by the mapper, or the default
+    /// skin's when there are none.
+    pub fn effective_combo_colours(&self) -> Vec<Colour> {
+        if self.combo_colours.is_empty() {
+            DEFAULT_COMBO_COLOURS.to_vec()
+        } else {
+            self.combo_colours.values().copied().collect()
+        }
+    }
+
+    /// The colour of the `combo_index`-th combo, wrapping around the effective combo colours.
+    pub fn combo_colour(&self, combo_index: usize) -> Colour {
+        let colours = self.effective_combo_colours();
+        colours[combo_index % colours.len()]
+    }
+}
+
+impl fmt::Display for Colours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, colour) in &self.combo_colours {
+            writeln!(f, "Combo{} : {}", index, colour)?;
+        }
+        if let Some(colour) = self.slider_track_override {
+            writeln!(f, "SliderTrackOverride : {}", colour)?;
+        }
+        if let Some(colour) = self.slider_border {
+            writeln!(f, "SliderBorder : {}", colour)?;
+        }
+        for (key, value) in &self.extras {
+            writeln!(f, "{} : {}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse_colours(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let (k, v) = split_key_value(line, FileSections::Colours)?;
+    let value =
+        || Colour::from_str(v).map_err(|_| ParseError::invalid_key(FileSections::Colours, line, k));
+
+    match k {
+        "SliderTrackOverride" => beatmap.colours.slider_track_override = Some(value()?),
+        "SliderBorder" => beatmap.colours.slider_border = Some(value()?),
+        _ => match combo_index(k) {
+            Some(index) => {
+                beatmap.colours.combo_colours.insert(index, value()?);
+            }
+            None => beatmap
+                .colours
+                .extras
+                .push((String::from(k), String::from(v))),
+        },
+    }
+
+    Ok(())
+}
+
+/// Whether `key` is one of the `[Colours]` keys this crate reads into [`Colours`].
+pub(crate) fn is_known_key(key: &str) -> bool {
+    matches!(key, "SliderTrackOverride" | "SliderBorder") || combo_index(key).is_some()
+}
+
+fn combo_index(key: &str) -> Option<u32> {
+    key.strip_prefix("Combo")?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colours() {
+        let test_str = "Combo2 : 0,202,0
+            Combo1 : 255,192,0
+            Combo3 : 18,124,255,128
+            SliderTrackOverride : 10,20,30
+            SliderBorder : 255,255,255";
+        let mut beatmap = Beatmap::default();
+        for line in test_str.lines() {
+            parse_colours(line, &mut beatmap).unwrap();
+        }
+
+        assert_eq!(
+            beatmap.colours,
+            Colours {
+                combo_colours: BTreeMap::from([
+                    (1, Colour::new(255, 192, 0)),
+                    (2, Colour::new(0, 202, 0)),
+                    (3, Colour::with_alpha(18, 124, 255, 128)),
+                ]),
+                slider_track_override: Some(Colour::new(10, 20, 30)),
+                slider_border: Some(Colour::new(255, 255, 255)),
+                extras: Vec::new(),
+            }
+        );
+        assert_eq!(
+            beatmap.colours.effective_combo_colours(),
+            vec![
+                Colour::new(255, 192, 0),
+                Colour::new(0, 202, 0),
+                Colour::with_alpha(18, 124, 255, 128),
+            ]
+        );
+        assert_eq!(beatmap.colours.combo_colour(4), Colour::new(0, 202, 0));
+    }
+
+    #[test]
+    fn test_unknown_colours() {
+        let mut beatmap = Beatmap::default();
+        parse_colours("Combo1 : 255,192,0", &mut beatmap).unwrap();
+        parse_colours("SliderBody : 1,2,3", &mut beatmap).unwrap();
+
+        assert_eq!(
+            beatmap.colours.extras,
+            vec![(String::from("SliderBody"), String::from("1,2,3"))]
+        );
+        assert_eq!(
+            beatmap.colours.to_string(),
+            "Combo1 : 255,192,0\nSliderBody : 1,2,3\n"
+        );
+    }
+
+    #[test]
+    fn test_default_combo_colours() {
+        let colours = Colours::default();
+
+        assert_eq!(
+            colours.effective_combo_colours(),
+            DEFAULT_COMBO_COLOURS.to_vec()
+        );
+    }
+}
@@ -1,13 +1,29 @@
-use crate::Beatmap;
+use crate::{
+    error::{split_key_value, ParseError},
+    events::Break,
+    file_sections::FileSections,
+    general::Mode,
+    time::Time,
+    Beatmap,
+};
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+/// How far the lowest health of a simulated full combo may be from its target for a drain rate
+/// to be accepted.
+const MINIMUM_HEALTH_ERROR: f64 = 0.01;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Difficulty {
     pub hp: f32,
     pub cs: f32,
     pub od: f32,
     pub ar: f32,
-    pub slider_multiplier: f32,
-    pub slider_tickrate: f32,
+    /// Read as doubles like the game does, since slider durations are computed from them.
+    pub slider_multiplier: f64,
+    pub slider_tickrate: f64,
+    /// Keys this crate doesn't know, in file order, written back after the known ones.
+    pub extras: Vec<(String, String)>,
 }
 
 impl Difficulty {
@@ -16,8 +32,8 @@ impl Difficulty {
         cs: f32,
         od: f32,
         ar: f32,
-        slider_multiplier: f32,
-        slider_tickrate: f32,
+        slider_multiplier: f64,
+        slider_tickrate: f64,
     ) -> Self {
         Self {
             hp,
@@ -26,8 +42,219 @@ impl Difficulty {
             ar,
             slider_multiplier,
             slider_tickrate,
+            extras: Vec::new(),
+        }
+    }
+
+    /// `slider_multiplier` as the game uses it, clamped between 0.4x and 3.6x so slider durations
+    /// stay finite. A value that isn't a number counts as the lowest.
+    pub fn effective_slider_multiplier(&self) -> f64 {
+        match self.slider_multiplier.is_nan() {
+            true => 0.4,
+            false => self.slider_multiplier.clamp(0.4, 3.6),
+        }
+    }
+
+    /// How long before its time an object appears, from the approach rate.
+    pub fn preempt(&self) -> Time {
+        Time::from_ms(difficulty_range(f64::from(self.ar), 1800.0, 1200.0, 450.0))
+    }
+
+    /// How long an object takes to fade in once it appears, from the approach rate.
+    pub fn fade_in(&self) -> Time {
+        Time::from_ms(difficulty_range(f64::from(self.ar), 1200.0, 800.0, 300.0))
+    }
+
+    /// [`Difficulty::preempt`] in real time when the song plays at `rate`, e.g. 1.5 for DT.
+    pub fn preempt_at_rate(&self, rate: f64) -> Time {
+        self.preempt() * rate.recip()
+    }
+
+    pub fn fade_in_at_rate(&self, rate: f64) -> Time {
+        self.fade_in() * rate.recip()
+    }
+
+    /// The approach rate that gives the preempt of this one played at `rate`, as difficulty
+    /// calculators report it for DT and HT.
+    pub fn approach_rate_at_rate(&self, rate: f64) -> f64 {
+        match self.preempt_at_rate(rate).as_ms() {
+            t if t > 1200.0 => 5.0 - (t - 1200.0) / 120.0,
+            t => 5.0 + (1200.0 - t) / 150.0,
+        }
+    }
+
+    /// How early or late each judgement can be hit, from the overall difficulty. Catch has no
+    /// timing windows.
+    pub fn hit_windows(&self, mode: Mode) -> Option<HitWindows> {
+        let od = f64::from(self.od);
+        let range = |min, mid, max| Time::from_ms(difficulty_range(od, min, mid, max));
+        let mania = |base: f64| Time::from_ms(base - 3.0 * od);
+
+        match mode {
+            Mode::Osu => Some(HitWindows {
+                max: None,
+                hit300: range(80.0, 50.0, 20.0),
+                hit200: None,
+                hit100: Some(range(140.0, 100.0, 60.0)),
+                hit50: Some(range(200.0, 150.0, 100.0)),
+                miss: Time::from(400),
+            }),
+            Mode::Taiko => Some(HitWindows {
+                max: None,
+                hit300: range(50.0, 35.0, 20.0),
+                hit200: None,
+                hit100: Some(range(120.0, 80.0, 50.0)),
+                hit50: None,
+                miss: range(135.0, 95.0, 70.0),
+            }),
+            Mode::Catch => None,
+            Mode::Mania => Some(HitWindows {
+                max: Some(Time::from(16)),
+                hit300: mania(64.0),
+                hit200: Some(mania(97.0)),
+                hit100: Some(mania(127.0)),
+                hit50: Some(mania(151.0)),
+                miss: mania(188.0),
+            }),
         }
     }
+
+    /// The overall difficulty that gives the osu!standard 300 window of this one played at
+    /// `rate`, as difficulty calculators report it for DT and HT.
+    pub fn overall_difficulty_at_rate(&self, rate: f64) -> f64 {
+        let hit300 = self
+            .hit_windows(Mode::Osu)
+            .map_or(0.0, |t| t.hit300.as_ms());
+        (80.0 - hit300 / rate) / 6.0
+    }
+
+    /// The radius of hit circles in osu!pixels, from the circle size.
+    pub fn circle_radius(&self) -> f64 {
+        32.0 * self.scale()
+    }
+
+    /// The width of the catcher's plate that catches fruits in osu!pixels, from the circle size.
+    pub fn catch_width(&self) -> f64 {
+        106.75 * self.scale() * 0.8
+    }
+
+    /// The lowest health osu!lazer's drain aims to leave a player who hits every object, from
+    /// 0.95 at HP 0 to 0.3 at HP 10.
+    pub fn health_drain_target(&self) -> f64 {
+        difficulty_range(f64::from(self.hp), 0.95, 0.7, 0.3)
+    }
+
+    /// The health drained per millisecond, binary searched like osu!lazer does until a full combo
+    /// bottoms out at [`Difficulty::health_drain_target`].
+    ///
+    /// `judgements` are the times of a full combo's judgements, sorted, with the health each one
+    /// gives back. Health drains from `drain_start`, but not between judgements a break ends
+    /// between. [`Beatmap::drain_rate`](crate::Beatmap::drain_rate) fills these in from a beatmap.
+    pub fn drain_rate(
+        &self,
+        drain_start: Time,
+        judgements: &[(Time, f64)],
+        breaks: &[Break],
+    ) -> f64 {
+        if judgements.len() <= 1 {
+            return 0.0;
+        }
+
+        let target = self.health_drain_target();
+        let mut adjustment: i32 = 1;
+        let mut rate = 1.0;
+
+        loop {
+            let mut health: f64 = 1.0;
+            let mut lowest: f64 = 1.0;
+            let mut next_break = 0;
+
+            for (index, &(time, increase)) in judgements.iter().enumerate() {
+                let mut last_time = match index.checked_sub(1) {
+                    Some(t) => judgements[t].0,
+                    None => drain_start,
+                };
+                while next_break < breaks.len() && breaks[next_break].end_time <= time {
+                    last_time = time;
+                    next_break += 1;
+                }
+
+                health -= (time - last_time).as_ms() * rate;
+                lowest = lowest.min(health);
+                health = (health + increase).min(1.0);
+
+                // the rate is too harsh already
+                if lowest < 0.0 {
+                    break;
+                }
+            }
+
+            if (lowest - target).abs() <= MINIMUM_HEALTH_ERROR {
+                return rate;
+            }
+
+            // each step is half the last, towards the target
+            adjustment = match adjustment.checked_mul(2) {
+                Some(t) => t,
+                None => return rate,
+            };
+            rate += (lowest - target).signum() / f64::from(adjustment);
+        }
+    }
+
+    /// The full spins a spinner lasting `duration` needs to be cleared, from the overall
+    /// difficulty. For DT or HT, pass the spinner's duration divided by the rate.
+    pub fn spins_required(&self, duration: Time) -> u32 {
+        let spins_per_second = difficulty_range(f64::from(self.od), 1.5, 2.5, 3.75);
+        (duration.as_ms() / 1000.0 * spins_per_second).max(0.0) as u32
+    }
+
+    /// The scale of circles and the catcher, 1 at circle size 5.
+    fn scale(&self) -> f64 {
+        1.0 - 0.7 * (f64::from(self.cs) - 5.0) / 5.0
+    }
+}
+
+/// The timing windows of each judgement: an object hit within a window of its time, before or
+/// after, gets that judgement. Modes don't use every judgement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitWindows {
+    /// Mania's rainbow 300.
+    pub max: Option<Time>,
+    pub hit300: Time,
+    /// Only used by mania.
+    pub hit200: Option<Time>,
+    pub hit100: Option<Time>,
+    /// Not used by taiko.
+    pub hit50: Option<Time>,
+    /// Hits earlier than this are ignored rather than counted as misses.
+    pub miss: Time,
+}
+
+impl HitWindows {
+    /// The windows in real time when the song plays at `rate`, e.g. 1.5 for DT.
+    pub fn at_rate(&self, rate: f64) -> Self {
+        let scale = |t: Time| t * rate.recip();
+
+        Self {
+            max: self.max.map(scale),
+            hit300: scale(self.hit300),
+            hit200: self.hit200.map(scale),
+            hit100: self.hit100.map(scale),
+            hit50: self.hit50.map(scale),
+            miss: scale(self.miss),
+        }
+    }
+}
+
+/// Maps a difficulty setting from 0 to 10 linearly to `min`, `mid` at 5, and `max`.
+fn difficulty_range(value: f64, min: f64, mid: f64, max: f64) -> f64 {
+    match value {
+        t if t > 5.0 => mid + (max - mid) * (t - 5.0) / 5.0,
+        t if t < 5.0 => mid - (mid - min) * (5.0 - t) / 5.0,
+        _ => mid,
+    }
 }
 
 impl Default for Difficulty {
@@ -37,31 +264,53 @@ impl Default for Difficulty {
             cs: 5.0,
             od: 5.0,
             ar: 5.0,
-            slider_multiplier: 1.0,
-            slider_tickrate: 1.4,
+            slider_multiplier: 1.4,
+            slider_tickrate: 1.0,
+            extras: Vec::new(),
         }
     }
 }
 
-pub fn parse_difficulty(line: &str, beatmap: &mut Beatmap) {
-    let (k, v) = line.split_once(':').unwrap();
-    match k.trim() {
-        "HPDrainRate" => beatmap.difficulty.hp = v.trim().parse::<f32>().unwrap(),
-        "CircleSize" => beatmap.difficulty.cs = v.trim().parse::<f32>().unwrap(),
-        "OverallDifficulty" => beatmap.difficulty.od = v.trim().parse::<f32>().unwrap(),
-        "ApproachRate" => beatmap.difficulty.ar = v.trim().parse::<f32>().unwrap(),
-        "SliderMultiplier" => {
-            beatmap.difficulty.slider_multiplier = v.trim().parse::<f32>().unwrap()
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "HPDrainRate:{}", self.hp)?;
+        writeln!(f, "CircleSize:{}", self.cs)?;
+        writeln!(f, "OverallDifficulty:{}", self.od)?;
+        writeln!(f, "ApproachRate:{}", self.ar)?;
+        writeln!(f, "SliderMultiplier:{}", self.slider_multiplier)?;
+        writeln!(f, "SliderTickRate:{}", self.slider_tickrate)?;
+        for (key, value) in &self.extras {
+            writeln!(f, "{}:{}", key, value)?;
         }
-        "SliderTickRate" => beatmap.difficulty.slider_tickrate = v.trim().parse::<f32>().unwrap(),
-        _ => {}
+
+        Ok(())
+    }
+}
+
+pub fn parse_difficulty(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let (k, v) = split_key_value(line, FileSections::Difficulty)?;
+    let invalid = |_| ParseError::invalid_key(FileSections::Difficulty, line, k);
+
+    match k {
+        "HPDrainRate" => beatmap.difficulty.hp = v.parse().map_err(invalid)?,
+        "CircleSize" => beatmap.difficulty.cs = v.parse().map_err(invalid)?,
+        "OverallDifficulty" => beatmap.difficulty.od = v.parse().map_err(invalid)?,
+        "ApproachRate" => beatmap.difficulty.ar = v.parse().map_err(invalid)?,
+        "SliderMultiplier" => beatmap.difficulty.slider_multiplier = v.parse().map_err(invalid)?,
+        "SliderTickRate" => beatmap.difficulty.slider_tickrate = v.parse().map_err(invalid)?,
+        _ => beatmap
+            .difficulty
+            .extras
+            .push((String::from(k), String::from(v))),
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Editor, Format, General, Metadata};
+    use crate::{Colours, Editor, Format, General, Metadata, Storyboard};
 
     #[test]
     fn test_parse_difficulty() {
@@ -73,7 +322,7 @@ mod tests {
             SliderTickRate:2.0";
         let mut beatmap = Beatmap::default();
         for line in test_str.lines() {
-            parse_difficulty(line, &mut beatmap);
+            parse_difficulty(line, &mut beatmap).unwrap();
         }
 
         assert_eq!(
@@ -90,10 +339,42 @@ mod tests {
                     ar: 9.8,
                     slider_multiplier: 1.4,
                     slider_tickrate: 2.0,
+                    extras: Vec::new(),
                 },
+                events: Vec::new(),
+                storyboard: Storyboard::default(),
                 timing_points: Vec::new(),
+                colours: Colours::default(),
                 hit_objects: Vec::new(),
+                unknown_sections: Vec::new(),
             }
         );
     }
+
+    #[test]
+    fn test_difficulty_attributes() {
+        let difficulty = Difficulty::new(5.0, 4.0, 8.0, 9.0, 1.4, 1.0);
+
+        assert_eq!(difficulty.preempt(), Time::from(600));
+        assert_eq!(difficulty.fade_in(), Time::from(400));
+        assert_eq!(difficulty.preempt_at_rate(1.5), Time::from(400));
+        assert!((difficulty.approach_rate_at_rate(1.5) - 10.333333333333334).abs() < 1e-9);
+        assert!((difficulty.circle_radius() - 36.48).abs() < 1e-9);
+        assert!((difficulty.catch_width() - 97.3560).abs() < 1e-9);
+        assert_eq!(difficulty.health_drain_target(), 0.7);
+        assert_eq!(difficulty.spins_required(Time::from(2000)), 6);
+
+        let hit_windows = difficulty.hit_windows(Mode::Osu).unwrap();
+        assert_eq!(hit_windows.hit300, Time::from(32));
+        assert_eq!(hit_windows.hit100, Some(Time::from(76)));
+        assert_eq!(hit_windows.hit50, Some(Time::from(120)));
+        assert_eq!(hit_windows.at_rate(2.0).hit300, Time::from(16));
+        assert!((difficulty.overall_difficulty_at_rate(1.5) - 9.777777777777779).abs() < 1e-9);
+
+        let hit_windows = difficulty.hit_windows(Mode::Mania).unwrap();
+        assert_eq!(hit_windows.max, Some(Time::from(16)));
+        assert_eq!(hit_windows.hit200, Some(Time::from(73)));
+        assert_eq!(hit_windows.miss, Time::from(164));
+        assert_eq!(difficulty.hit_windows(Mode::Catch), None);
+    }
 }
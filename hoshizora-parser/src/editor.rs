@@ -1,17 +1,26 @@
-use crate::Beatmap;
+use crate::{
+    error::{split_key_value, ParseError},
+    file_sections::FileSections,
+    time::Time,
+    Beatmap,
+};
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Editor {
-    pub bookmarks: Vec<u32>,
+    pub bookmarks: Vec<Time>,
     pub distance_spacing: f32,
     pub beat_divisor: u8,
     pub grid_size: u8,
     pub timeline_zoom: f32,
+    /// Keys this crate doesn't know, in file order, written back after the known ones.
+    pub extras: Vec<(String, String)>,
 }
 
 impl Editor {
     pub fn new(
-        bookmarks: Vec<u32>,
+        bookmarks: Vec<Time>,
         distance_spacing: f32,
         beat_divisor: u8,
         grid_size: u8,
@@ -23,6 +32,7 @@ impl Editor {
             beat_divisor,
             grid_size,
             timeline_zoom,
+            extras: Vec::new(),
         }
     }
 }
@@ -35,32 +45,60 @@ impl Default for Editor {
             beat_divisor: 4,
             grid_size: 4,
             timeline_zoom: 1.0,
+            extras: Vec::new(),
         }
     }
 }
 
-pub fn parse_editor(line: &str, beatmap: &mut Beatmap) {
-    let (k, v) = line.trim().split_once(':').unwrap();
-    match k.trim() {
+impl fmt::Display for Editor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.bookmarks.is_empty() {
+            let bookmarks: Vec<String> = self.bookmarks.iter().map(|t| t.to_string()).collect();
+            writeln!(f, "Bookmarks: {}", bookmarks.join(","))?;
+        }
+        writeln!(f, "DistanceSpacing: {}", self.distance_spacing)?;
+        writeln!(f, "BeatDivisor: {}", self.beat_divisor)?;
+        writeln!(f, "GridSize: {}", self.grid_size)?;
+        writeln!(f, "TimelineZoom: {}", self.timeline_zoom)?;
+        for (key, value) in &self.extras {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse_editor(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let (k, v) = split_key_value(line, FileSections::Editor)?;
+    let invalid = || ParseError::invalid_key(FileSections::Editor, line, k);
+
+    match k {
         "Bookmarks" => {
             beatmap.editor.bookmarks = v
-                .trim()
                 .split(',')
-                .map(|x| x.parse::<u32>().unwrap())
-                .collect()
+                .filter(|x| !x.trim().is_empty())
+                .map(|x| x.parse::<Time>().map_err(|_| invalid()))
+                .collect::<Result<_, _>>()?
         }
-        "DistanceSpacing" => beatmap.editor.distance_spacing = v.trim().parse::<f32>().unwrap(),
-        "BeatDivisor" => beatmap.editor.beat_divisor = v.trim().parse::<u8>().unwrap(),
-        "GridSize" => beatmap.editor.grid_size = v.trim().parse::<u8>().unwrap(),
-        "TimelineZoom" => beatmap.editor.timeline_zoom = v.trim().parse::<f32>().unwrap(),
-        _ => {}
+        "DistanceSpacing" => {
+            beatmap.editor.distance_spacing = v.parse::<f32>().map_err(|_| invalid())?
+        }
+        "BeatDivisor" => beatmap.editor.beat_divisor = v.parse::<u8>().map_err(|_| invalid())?,
+        "GridSize" => beatmap.editor.grid_size = v.parse::<u8>().map_err(|_| invalid())?,
+        "TimelineZoom" => beatmap.editor.timeline_zoom = v.parse::<f32>().map_err(|_| invalid())?,
+        _ => beatmap
+            .editor
+            .extras
+            .push((String::from(k), String::from(v))),
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Difficulty, Format, General, Metadata};
+    use crate::{Colours, Difficulty, Format, General, Metadata, Storyboard};
 
     #[test]
     fn test_parse_editor() {
@@ -70,7 +108,7 @@ mod tests {
         TimelineZoom: 3.2";
         let mut beatmap = Beatmap::default();
         for line in test_str.lines() {
-            parse_editor(line, &mut beatmap);
+            parse_editor(line, &mut beatmap).unwrap();
         }
 
         assert_eq!(
@@ -84,11 +122,16 @@ mod tests {
                     beat_divisor: 4,
                     grid_size: 32,
                     timeline_zoom: 3.2,
+                    extras: Vec::new(),
                 },
                 metadata: Metadata::default(),
                 difficulty: Difficulty::default(),
+                events: Vec::new(),
+                storyboard: Storyboard::default(),
                 timing_points: Vec::new(),
+                colours: Colours::default(),
                 hit_objects: Vec::new(),
+                unknown_sections: Vec::new(),
             }
         )
     }
@@ -0,0 +1,226 @@
+use crate::file_sections::FileSections;
+use std::{error::Error, fmt, io, str::FromStr};
+
+/// The part of a line that failed to parse.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    /// A `Key: value` pair, identified by its key.
+    Key(String),
+    /// A comma-separated line, identified by the 0-based index of the column.
+    Column(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    /// A `Key: value` or `$name=value` line without its separator.
+    MissingSeparator,
+    /// A required column is absent.
+    MissingField,
+    /// A value could not be converted to its expected type.
+    InvalidValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number in the parsed text, or 0 if the line was parsed on its own.
+    pub line: usize,
+    pub section: FileSections,
+    pub field: Option<Field>,
+    pub kind: ParseErrorKind,
+    /// The raw text of the offending line.
+    pub text: String,
+}
+
+impl ParseError {
+    pub fn new(
+        section: FileSections,
+        text: &str,
+        field: Option<Field>,
+        kind: ParseErrorKind,
+    ) -> Self {
+        Self {
+            line: 0,
+            section,
+            field,
+            kind,
+            text: String::from(text),
+        }
+    }
+
+    pub fn missing_separator(section: FileSections, text: &str) -> Self {
+        Self::new(section, text, None, ParseErrorKind::MissingSeparator)
+    }
+
+    pub fn invalid_key(section: FileSections, text: &str, key: &str) -> Self {
+        Self::new(
+            section,
+            text,
+            Some(Field::Key(String::from(key))),
+            ParseErrorKind::InvalidValue,
+        )
+    }
+
+    pub fn column(section: FileSections, text: &str, index: usize, kind: ParseErrorKind) -> Self {
+        Self::new(section, text, Some(Field::Column(index)), kind)
+    }
+
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = line;
+        self
+    }
+
+    /// Describes what went wrong, without the line number and section.
+    pub fn message(&self) -> String {
+        let kind = match self.kind {
+            ParseErrorKind::MissingSeparator => "missing separator",
+            ParseErrorKind::MissingField => "missing field",
+            ParseErrorKind::InvalidValue => "invalid value",
+        };
+        match &self.field {
+            Some(Field::Key(key)) => format!("{} for `{}`", kind, key),
+            Some(Field::Column(index)) => format!("{} at column {}", kind, index),
+            None => String::from(kind),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: {} in [{:?}]: `{}`",
+            self.line,
+            self.message(),
+            self.section,
+            self.text.trim()
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// An error while parsing a beatmap from a reader or a file.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    /// The bytes are neither UTF-8 nor UTF-16, and no legacy encoding was configured, or they
+    /// are malformed in the detected encoding.
+    InvalidEncoding,
+    Parse(ParseError),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::InvalidEncoding => write!(f, "unrecognised text encoding"),
+            ReadError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::InvalidEncoding => None,
+            ReadError::Parse(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> Self {
+        ReadError::Parse(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The line was understood, but looks suspicious.
+    Warning,
+    /// The line could not be parsed and was skipped.
+    Error,
+}
+
+/// A problem found while parsing in [`ParseMode::Lenient`](crate::ParseMode::Lenient), or a
+/// warning found in either mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line number in the parsed text.
+    pub line: usize,
+    pub section: FileSections,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, line: usize, section: FileSections, message: String) -> Self {
+        Self {
+            severity,
+            line,
+            section,
+            message,
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        Self {
+            severity: Severity::Error,
+            line: err.line,
+            section: err.section,
+            message: format!("{}: `{}`", err.message(), err.text.trim()),
+        }
+    }
+}
+
+/// Splits a `Key: value` line, trimming both halves.
+pub(crate) fn split_key_value(
+    line: &str,
+    section: FileSections,
+) -> Result<(&str, &str), ParseError> {
+    line.split_once(':')
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .ok_or_else(|| ParseError::missing_separator(section, line))
+}
+
+/// Parses the column at `index` of an already split comma-separated line.
+pub(crate) fn parse_column<T: FromStr>(params: &[&str], index: usize) -> Result<T, ParseErrorKind> {
+    params
+        .get(index)
+        .ok_or(ParseErrorKind::MissingField)?
+        .trim()
+        .parse::<T>()
+        .map_err(|_| ParseErrorKind::InvalidValue)
+}
+
+/// Falls back to the default value when an optional trailing column is absent.
+pub(crate) fn default_if_missing<T: Default>(kind: ParseErrorKind) -> Result<T, ParseErrorKind> {
+    match kind {
+        ParseErrorKind::MissingField => Ok(T::default()),
+        _ => Err(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let err = ParseError::invalid_key(FileSections::General, "Countdown: 7", "Countdown")
+            .with_line(8);
+        assert_eq!(
+            err.to_string(),
+            "line 8: invalid value for `Countdown` in [General]: `Countdown: 7`"
+        );
+    }
+}
@@ -1,47 +1,195 @@
-use crate::Beatmap;
+use crate::{
+    error::{default_if_missing, parse_column, ParseError, ParseErrorKind},
+    file_sections::FileSections,
+    format::Format,
+    storyboard::{is_storyboard_line, parse_storyboard_line},
+    time::Time,
+    Beatmap,
+};
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
-pub enum EventType {
-    Background,
-    Video,
-    Break,
-    Storyboard,
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Event {
+    Background(Background),
+    Video(Video),
+    Break(Break),
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Event {
-    pub event_type: EventType,
-    pub start_time: u32,
-    pub event_params: Vec<String>,
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Background {
+    pub filename: String,
+    pub x_offset: i32,
+    pub y_offset: i32,
 }
 
-impl Event {
-    fn new(event_type: EventType, start_time: u32, event_params: Vec<String>) -> Self {
-        Self {
-            event_type,
-            start_time,
-            event_params,
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Video {
+    pub start_time: Time,
+    pub filename: String,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Break {
+    pub start_time: Time,
+    pub end_time: Time,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Background(t) => {
+                write!(f, "0,0,\"{}\",{},{}", t.filename, t.x_offset, t.y_offset)
+            }
+            Event::Video(t) => write!(
+                f,
+                "Video,{},\"{}\",{},{}",
+                t.start_time, t.filename, t.x_offset, t.y_offset
+            ),
+            Event::Break(t) => write!(f, "2,{},{}", t.start_time, t.end_time),
         }
     }
 }
 
-impl Default for Event {
-    fn default() -> Self {
-        Self {
-            event_type: EventType::Background,
-            start_time: 0,
-            event_params: Vec::new(),
+pub fn parse_events(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let line = &*beatmap.storyboard.expand_variables(line);
+    if is_storyboard_line(line) {
+        return parse_storyboard_line(line, &mut beatmap.storyboard, &beatmap.format);
+    }
+
+    if let Some(event) = parse_event(line, &beatmap.format)? {
+        beatmap.events.push(event);
+    }
+
+    Ok(())
+}
+
+/// Parses an event that isn't part of the storyboard, or returns `None` for the ones the game
+/// ignores.
+pub fn parse_event(line: &str, format: &Format) -> Result<Option<Event>, ParseError> {
+    let params = split_event_params(line);
+    let column = |index| move |kind| ParseError::column(FileSections::Events, line, index, kind);
+    let offset = format.offset();
+
+    let event = match params[0] {
+        "0" | "Background" => Event::Background(Background {
+            filename: parse_filename(&params, 2).map_err(column(2))?,
+            x_offset: parse_column(&params, 3)
+                .or_else(default_if_missing)
+                .map_err(column(3))?,
+            y_offset: parse_column(&params, 4)
+                .or_else(default_if_missing)
+                .map_err(column(4))?,
+        }),
+        "1" | "Video" => Event::Video(Video {
+            start_time: Time::from(parse_column::<Time>(&params, 1).map_err(column(1))?.round())
+                + offset,
+            filename: parse_filename(&params, 2).map_err(column(2))?,
+            x_offset: parse_column(&params, 3)
+                .or_else(default_if_missing)
+                .map_err(column(3))?,
+            y_offset: parse_column(&params, 4)
+                .or_else(default_if_missing)
+                .map_err(column(4))?,
+        }),
+        "2" | "Break" => Event::Break(Break {
+            start_time: Time::from(parse_column::<Time>(&params, 1).map_err(column(1))?.round())
+                + offset,
+            end_time: Time::from(parse_column::<Time>(&params, 2).map_err(column(2))?.round())
+                + offset,
+        }),
+        // legacy background colour events
+        "3" => return Ok(None),
+        _ => return Err(column(0)(ParseErrorKind::InvalidValue)),
+    };
+
+    Ok(Some(event))
+}
+
+/// Splits an event line on commas, ignoring the ones inside double-quoted filenames.
+pub fn split_event_params(line: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                params.push(line[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
         }
     }
+    params.push(line[start..].trim());
+
+    params
 }
 
-pub fn parse_events(line: &str, beatmap: &mut Beatmap) {
-    let parsed: Vec<&str> = line.split(',').collect();
-    let event = Event::default();
-    event.event_type = match parsed[0].trim() {
-        "0" => EventType::Background,
-        "1" | "Video" => EventType::Video,
-        "2" | "Break" => EventType::Break,
-        _ => todo!(),
+pub(crate) fn parse_filename(params: &[&str], index: usize) -> Result<String, ParseErrorKind> {
+    params
+        .get(index)
+        .map(|t| String::from(t.trim_matches('"')))
+        .ok_or(ParseErrorKind::MissingField)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        let test_lines = [
+            "0,0,\"end, time.jpg\",0,0",
+            "Video,-200,\"intro.avi\",10,-5",
+            "1,500,video.mp4",
+            "2,10000,12000",
+            "Break,30000,35000",
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " F,0,0,1000,0,1",
+        ];
+        let mut beatmap = Beatmap::default();
+        for line in test_lines {
+            parse_events(line, &mut beatmap).unwrap();
+        }
+
+        assert_eq!(beatmap.storyboard.objects.len(), 1);
+        assert_eq!(beatmap.storyboard.objects[0].commands().len(), 1);
+        assert_eq!(
+            beatmap.events,
+            vec![
+                Event::Background(Background {
+                    filename: String::from("end, time.jpg"),
+                    x_offset: 0,
+                    y_offset: 0,
+                }),
+                Event::Video(Video {
+                    start_time: Time::from(-200),
+                    filename: String::from("intro.avi"),
+                    x_offset: 10,
+                    y_offset: -5,
+                }),
+                Event::Video(Video {
+                    start_time: Time::from(500),
+                    filename: String::from("video.mp4"),
+                    x_offset: 0,
+                    y_offset: 0,
+                }),
+                Event::Break(Break {
+                    start_time: Time::from(10000),
+                    end_time: Time::from(12000),
+                }),
+                Event::Break(Break {
+                    start_time: Time::from(30000),
+                    end_time: Time::from(35000),
+                }),
+            ]
+        );
     }
 }
@@ -1,7 +1,10 @@
 use std::str::FromStr;
 use strum_macros::EnumString;
 
-#[derive(Debug, PartialEq, EnumString)]
+/// A UTF-8 byte order mark, as decoded at the start of a file.
+pub(crate) const BOM: char = '\u{feff}';
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString)]
 pub enum FileSections {
     None,
     Format,
@@ -9,6 +12,7 @@ pub enum FileSections {
     Editor,
     Metadata,
     Difficulty,
+    Variables,
     Events,
     TimingPoints,
     Colours,
@@ -16,7 +20,16 @@ pub enum FileSections {
 }
 
 impl FileSections {
+    /// Whether the line is a `[Section]` header, known or not.
+    pub fn is_header(line: &str) -> bool {
+        let line = line.trim_start_matches(BOM);
+        let trimmed_line = line.trim();
+        line.starts_with("osu file format")
+            || (trimmed_line.starts_with('[') && trimmed_line.ends_with(']'))
+    }
+
     pub fn get_section(line: &str) -> Self {
+        let line = line.trim_start_matches(BOM);
         if line.starts_with("osu file format") {
             return FileSections::Format;
         }
@@ -46,4 +59,18 @@ mod tests {
         let test_str = "[Garbage]";
         assert_eq!(FileSections::get_section(test_str), FileSections::None);
     }
+
+    #[test]
+    fn test_is_header() {
+        assert!(FileSections::is_header("[Garbage]"));
+        assert!(FileSections::is_header("osu file format v14"));
+        assert!(!FileSections::is_header("Title:[Difficulty]"));
+    }
+
+    #[test]
+    fn test_parse_bom() {
+        let test_str = "\u{feff}osu file format v14";
+        assert!(FileSections::is_header(test_str));
+        assert_eq!(FileSections::get_section(test_str), FileSections::Format);
+    }
 }
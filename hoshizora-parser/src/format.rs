@@ -1,26 +1,87 @@
-use crate::Beatmap;
+use crate::{
+    error::{ParseError, ParseErrorKind},
+    file_sections::{FileSections, BOM},
+    time::Time,
+    Beatmap,
+};
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Format {
-    version: u32,
+    /// The `N` of the `osu file format vN` line.
+    pub version: u32,
 }
 
 impl Format {
+    pub const LATEST_VERSION: u32 = 14;
+
     pub fn new(version: u32) -> Self {
         Self { version }
     }
+
+    /// Milliseconds the game adds to every timestamp of files older than v5, which were timed
+    /// against a different audio offset.
+    pub fn offset(&self) -> Time {
+        match self.version {
+            0..=4 => Time::from(24),
+            _ => Time::ZERO,
+        }
+    }
 }
 
 impl Default for Format {
     fn default() -> Self {
-        Self { version: 14 }
+        Self {
+            version: Self::LATEST_VERSION,
+        }
+    }
+}
+
+/// Always writes the latest version, which is the only one the writer produces. Timestamps of
+/// older files have already been adjusted by [`Format::offset`] while parsing.
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "osu file format v{}", Self::LATEST_VERSION)
     }
 }
 
-pub fn parse_format(line: &str, beatmap: &mut Beatmap) {
-    beatmap.format.version = line
+pub fn parse_format(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    beatmap.format = parse_format_line(line)?;
+
+    Ok(())
+}
+
+/// Parses the `osu file format vN` line.
+pub fn parse_format_line(line: &str) -> Result<Format, ParseError> {
+    let version = line
         .trim()
+        .trim_start_matches(BOM)
         .trim_start_matches("osu file format v")
         .parse::<u32>()
-        .unwrap();
+        .map_err(|_| {
+            ParseError::new(
+                FileSections::Format,
+                line,
+                None,
+                ParseErrorKind::InvalidValue,
+            )
+        })?;
+
+    Ok(Format::new(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        let mut beatmap = Beatmap::default();
+        parse_format("osu file format v4", &mut beatmap).unwrap();
+
+        assert_eq!(beatmap.format, Format::new(4));
+        assert_eq!(beatmap.format.offset(), Time::from(24));
+        assert_eq!(Format::default().offset(), Time::ZERO);
+    }
 }
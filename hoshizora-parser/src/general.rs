@@ -1,9 +1,16 @@
-use crate::Beatmap;
-use std::str::FromStr;
+use crate::{
+    error::{split_key_value, ParseError},
+    file_sections::FileSections,
+    format::Format,
+    time::Time,
+    Beatmap,
+};
+use std::{fmt, str::FromStr};
 use strum::ParseError::VariantNotFound;
-use strum_macros::EnumString;
+use strum_macros::{Display, EnumString};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Countdown {
     None,
     Normal,
@@ -28,8 +35,17 @@ impl FromStr for Countdown {
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl fmt::Display for Countdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SampleSet {
+    /// Inherits the set from the timing point, or from `[General]` for timing points.
+    #[default]
     Default,
     Normal,
     Soft,
@@ -41,8 +57,8 @@ impl FromStr for SampleSet {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.trim() {
-            "0" => Ok(Self::Default),
-            "1" | "Normal" => Ok(Self::Default),
+            "0" | "None" => Ok(Self::Default),
+            "1" | "Normal" => Ok(Self::Normal),
             "2" | "Soft" => Ok(Self::Soft),
             "3" | "Drum" => Ok(Self::Drum),
             _ => Err(VariantNotFound),
@@ -50,7 +66,21 @@ impl FromStr for SampleSet {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// Writes the name used by the `[General]` section; timing points and hit samples use the
+/// numeric id instead.
+impl fmt::Display for SampleSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "None"),
+            Self::Normal => write!(f, "Normal"),
+            Self::Soft => write!(f, "Soft"),
+            Self::Drum => write!(f, "Drum"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mode {
     Osu,
     Taiko,
@@ -75,19 +105,28 @@ impl FromStr for Mode {
     }
 }
 
-#[derive(Debug, PartialEq, EnumString)]
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, EnumString, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OverlayPosition {
     NoChange,
     Below,
     Above,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct General {
-    pub audio_filename: String,
+/// The `[General]` section. `S` is the type of its strings, `&str` for [`GeneralRef`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct General<S = String> {
+    pub audio_filename: S,
     pub audio_lead_in: u32,
-    pub audio_hash: String,
-    pub preview_time: i32,
+    pub audio_hash: S,
+    pub preview_time: Time,
     pub countdown: Countdown,
     pub sample_set: SampleSet,
     pub stack_leniency: f32,
@@ -97,21 +136,32 @@ pub struct General {
     pub use_skin_sprites: bool,
     pub always_show_playfield: bool,
     pub overlay_position: OverlayPosition,
-    pub skin_preference: String,
+    pub skin_preference: S,
     pub epilepsy_warning: bool,
     pub countdown_offset: u32,
     pub special_style: bool,
     pub widescreen_storyboard: bool,
     pub samples_match_playback_rate: bool,
+    /// Keys this crate doesn't know, in file order, written back after the known ones.
+    pub extras: Vec<(S, S)>,
 }
 
+/// [`General`] with its strings borrowed from the parsed text.
+pub type GeneralRef<'a> = General<&'a str>;
+
 impl General {
     pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Default> Default for General<S> {
+    fn default() -> Self {
         Self {
-            audio_filename: String::new(),
+            audio_filename: S::default(),
             audio_lead_in: 0,
-            audio_hash: String::new(),
-            preview_time: -1,
+            audio_hash: S::default(),
+            preview_time: Time::from(-1),
             countdown: Countdown::Normal,
             sample_set: SampleSet::Normal,
             stack_leniency: 0.7,
@@ -121,66 +171,163 @@ impl General {
             use_skin_sprites: false,
             always_show_playfield: false,
             overlay_position: OverlayPosition::NoChange,
-            skin_preference: String::new(),
+            skin_preference: S::default(),
             epilepsy_warning: false,
             countdown_offset: 0,
             special_style: false,
             widescreen_storyboard: false,
             samples_match_playback_rate: false,
+            extras: Vec::new(),
         }
     }
 }
 
-impl Default for General {
-    fn default() -> Self {
-        Self::new()
+impl<S: AsRef<str>> fmt::Display for General<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "AudioFilename: {}", self.audio_filename.as_ref())?;
+        writeln!(f, "AudioLeadIn: {}", self.audio_lead_in)?;
+        if !self.audio_hash.as_ref().is_empty() {
+            writeln!(f, "AudioHash: {}", self.audio_hash.as_ref())?;
+        }
+        writeln!(f, "PreviewTime: {}", self.preview_time)?;
+        writeln!(f, "Countdown: {}", self.countdown)?;
+        writeln!(f, "SampleSet: {}", self.sample_set)?;
+        writeln!(f, "StackLeniency: {}", self.stack_leniency)?;
+        writeln!(f, "Mode: {}", self.mode)?;
+        writeln!(
+            f,
+            "LetterboxInBreaks: {}",
+            u8::from(self.letterbox_in_breaks)
+        )?;
+        writeln!(
+            f,
+            "StoryFireInFront: {}",
+            u8::from(self.story_fire_in_front)
+        )?;
+        writeln!(f, "UseSkinSprites: {}", u8::from(self.use_skin_sprites))?;
+        writeln!(
+            f,
+            "AlwaysShowPlayfield: {}",
+            u8::from(self.always_show_playfield)
+        )?;
+        writeln!(f, "OverlayPosition: {}", self.overlay_position)?;
+        if !self.skin_preference.as_ref().is_empty() {
+            writeln!(f, "SkinPreference: {}", self.skin_preference.as_ref())?;
+        }
+        writeln!(f, "EpilepsyWarning: {}", u8::from(self.epilepsy_warning))?;
+        writeln!(f, "CountdownOffset: {}", self.countdown_offset)?;
+        writeln!(f, "SpecialStyle: {}", u8::from(self.special_style))?;
+        writeln!(
+            f,
+            "WidescreenStoryboard: {}",
+            u8::from(self.widescreen_storyboard)
+        )?;
+        writeln!(
+            f,
+            "SamplesMatchPlaybackRate: {}",
+            u8::from(self.samples_match_playback_rate)
+        )?;
+        for (key, value) in &self.extras {
+            writeln!(f, "{}: {}", key.as_ref(), value.as_ref())?;
+        }
+
+        Ok(())
     }
 }
 
-pub fn parse_general(line: &str, beatmap: &mut Beatmap) {
-    let (k, v) = line.split_once(':').unwrap();
-    match k.trim() {
-        "AudioFilename" => beatmap.general.audio_filename = String::from(v.trim()),
-        "AudioLeadIn" => beatmap.general.audio_lead_in = v.trim().parse::<u32>().unwrap(),
-        "AudioHash" => beatmap.general.audio_hash = String::from(v.trim()),
-        "PreviewTime" => beatmap.general.preview_time = v.trim().parse::<i32>().unwrap(),
-        "Countdown" => beatmap.general.countdown = Countdown::from_str(v.trim()).unwrap(),
-        "SampleSet" => beatmap.general.sample_set = SampleSet::from_str(v.trim()).unwrap(),
-        "StackLeniency" => beatmap.general.stack_leniency = v.trim().parse::<f32>().unwrap(),
-        "Mode" => beatmap.general.mode = Mode::from_str(v.trim()).unwrap(),
-        "LetterboxInBreaks" => {
-            beatmap.general.letterbox_in_breaks = v.trim().parse::<u8>().unwrap() != 0
-        }
-        "StoryFireInFront" => {
-            beatmap.general.story_fire_in_front = v.trim().parse::<u8>().unwrap() != 0
+pub fn parse_general(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    read_general(line, &mut beatmap.general, &beatmap.format)
+}
+
+impl GeneralRef<'_> {
+    pub fn into_owned(self) -> General {
+        General {
+            audio_filename: String::from(self.audio_filename),
+            audio_lead_in: self.audio_lead_in,
+            audio_hash: String::from(self.audio_hash),
+            preview_time: self.preview_time,
+            countdown: self.countdown,
+            sample_set: self.sample_set,
+            stack_leniency: self.stack_leniency,
+            mode: self.mode,
+            letterbox_in_breaks: self.letterbox_in_breaks,
+            story_fire_in_front: self.story_fire_in_front,
+            use_skin_sprites: self.use_skin_sprites,
+            always_show_playfield: self.always_show_playfield,
+            overlay_position: self.overlay_position,
+            skin_preference: String::from(self.skin_preference),
+            epilepsy_warning: self.epilepsy_warning,
+            countdown_offset: self.countdown_offset,
+            special_style: self.special_style,
+            widescreen_storyboard: self.widescreen_storyboard,
+            samples_match_playback_rate: self.samples_match_playback_rate,
+            extras: self
+                .extras
+                .into_iter()
+                .map(|(k, v)| (String::from(k), String::from(v)))
+                .collect(),
         }
-        "UseSkinSprites" => beatmap.general.use_skin_sprites = v.trim().parse::<u8>().unwrap() != 0,
-        "AlwaysShowPlayfield" => {
-            beatmap.general.always_show_playfield = v.trim().parse::<u8>().unwrap() != 0
+    }
+}
+
+/// Reads a line of `[General]` without copying any string out of it.
+pub fn parse_general_ref<'a>(
+    line: &'a str,
+    general: &mut GeneralRef<'a>,
+    format: &Format,
+) -> Result<(), ParseError> {
+    read_general(line, general, format)
+}
+
+/// Reads a line of `[General]` into either kind of [`General`], copying strings out of the line
+/// only when `S` owns them.
+fn read_general<'a, S: From<&'a str>>(
+    line: &'a str,
+    general: &mut General<S>,
+    format: &Format,
+) -> Result<(), ParseError> {
+    let (k, v) = split_key_value(line, FileSections::General)?;
+    let invalid = || ParseError::invalid_key(FileSections::General, line, k);
+    let flag = || v.parse::<u8>().map(|t| t != 0).map_err(|_| invalid());
+
+    match k {
+        "AudioFilename" => general.audio_filename = S::from(v),
+        "AudioLeadIn" => general.audio_lead_in = v.parse::<u32>().map_err(|_| invalid())?,
+        "AudioHash" => general.audio_hash = S::from(v),
+        "PreviewTime" => {
+            let preview_time = v.parse::<Time>().map_err(|_| invalid())?;
+            general.preview_time = match preview_time == Time::from(-1) {
+                true => preview_time,
+                false => preview_time + format.offset(),
+            }
         }
+        "Countdown" => general.countdown = Countdown::from_str(v).map_err(|_| invalid())?,
+        "SampleSet" => general.sample_set = SampleSet::from_str(v).map_err(|_| invalid())?,
+        "StackLeniency" => general.stack_leniency = v.parse::<f32>().map_err(|_| invalid())?,
+        "Mode" => general.mode = Mode::from_str(v).map_err(|_| invalid())?,
+        "LetterboxInBreaks" => general.letterbox_in_breaks = flag()?,
+        "StoryFireInFront" => general.story_fire_in_front = flag()?,
+        "UseSkinSprites" => general.use_skin_sprites = flag()?,
+        "AlwaysShowPlayfield" => general.always_show_playfield = flag()?,
         "OverlayPosition" => {
-            beatmap.general.overlay_position = OverlayPosition::from_str(v.trim()).unwrap()
+            general.overlay_position = OverlayPosition::from_str(v).map_err(|_| invalid())?
         }
-        "SkinPreference" => beatmap.general.skin_preference = String::from(v.trim()),
-        "EpilepsyWarning" => {
-            beatmap.general.epilepsy_warning = v.trim().parse::<u8>().unwrap() != 0
-        }
-        "CountdownOffset" => beatmap.general.countdown_offset = v.trim().parse::<u32>().unwrap(),
-        "SpecialStyle" => beatmap.general.special_style = v.trim().parse::<u8>().unwrap() != 0,
-        "WidescreenStoryboard" => {
-            beatmap.general.widescreen_storyboard = v.trim().parse::<u8>().unwrap() != 0
-        }
-        "SamplesMatchPlaybackRate" => {
-            beatmap.general.samples_match_playback_rate = v.trim().parse::<u8>().unwrap() != 0
-        }
-        _ => {}
+        "SkinPreference" => general.skin_preference = S::from(v),
+        "EpilepsyWarning" => general.epilepsy_warning = flag()?,
+        "CountdownOffset" => general.countdown_offset = v.parse::<u32>().map_err(|_| invalid())?,
+        "SpecialStyle" => general.special_style = flag()?,
+        "WidescreenStoryboard" => general.widescreen_storyboard = flag()?,
+        "SamplesMatchPlaybackRate" => general.samples_match_playback_rate = flag()?,
+        _ => general.extras.push((S::from(k), S::from(v))),
     }
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Difficulty, Editor, Format, Metadata};
+    use crate::{Colours, Difficulty, Editor, Format, Metadata, Storyboard};
 
     #[test]
     fn test_parse_general() {
@@ -205,7 +352,7 @@ mod tests {
             SamplesMatchPlaybackRate: 1";
         let mut beatmap = Beatmap::default();
         for line in test_str.lines() {
-            parse_general(line, &mut beatmap)
+            parse_general(line, &mut beatmap).unwrap();
         }
 
         assert_eq!(
@@ -216,7 +363,7 @@ mod tests {
                     audio_filename: String::from("audio.mp3"),
                     audio_lead_in: 0,
                     audio_hash: String::from("afjskldfjaldksfjklasf"),
-                    preview_time: 10,
+                    preview_time: Time::from(10),
                     countdown: Countdown::None,
                     sample_set: SampleSet::Drum,
                     stack_leniency: 0.75,
@@ -232,12 +379,17 @@ mod tests {
                     special_style: true,
                     widescreen_storyboard: true,
                     samples_match_playback_rate: true,
+                    extras: Vec::new(),
                 },
                 editor: Editor::default(),
                 metadata: Metadata::default(),
                 difficulty: Difficulty::default(),
+                events: Vec::new(),
+                storyboard: Storyboard::default(),
                 timing_points: Vec::new(),
+                colours: Colours::default(),
                 hit_objects: Vec::new(),
+                unknown_sections: Vec::new(),
             }
         );
     }
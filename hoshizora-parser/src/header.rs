@@ -0,0 +1,225 @@
+use crate::{
+    difficulty::{parse_difficulty, Difficulty},
+    error::{parse_column, Diagnostic, ParseError, ParseErrorKind},
+    file_sections::FileSections,
+    format::{parse_format_line, Format},
+    general::{parse_general_ref, General, GeneralRef},
+    metadata::{parse_metadata_ref, Metadata, MetadataRef},
+    options::{ParseMode, ParseOptions},
+    Beatmap,
+};
+
+/// The sections of a beatmap needed to list and search it.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BeatmapHeader {
+    pub format: Format,
+    pub general: General,
+    pub metadata: Metadata,
+    pub difficulty: Difficulty,
+    /// Only counted by [`parse_header_with_counts`].
+    pub object_counts: Option<ObjectCounts>,
+}
+
+/// The number of hit objects of each type.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ObjectCounts {
+    pub hit_circles: usize,
+    pub sliders: usize,
+    pub spinners: usize,
+    pub mania_holds: usize,
+}
+
+impl ObjectCounts {
+    pub fn total(&self) -> usize {
+        self.hit_circles + self.sliders + self.spinners + self.mania_holds
+    }
+}
+
+/// Parses `[General]`, `[Metadata]` and `[Difficulty]`, and stops reading after `[Difficulty]`.
+pub fn parse_header(text: &str) -> Result<BeatmapHeader, ParseError> {
+    parse_header_with_options(text, &ParseOptions::default()).map(|(header, _)| header)
+}
+
+/// Like [`parse_header`], but also reads on to `[HitObjects]` to count the hit objects, by their
+/// type column only.
+pub fn parse_header_with_counts(text: &str) -> Result<BeatmapHeader, ParseError> {
+    parse_header_with_counts_and_options(text, &ParseOptions::default()).map(|(header, _)| header)
+}
+
+/// Like [`parse_header`], returning the header together with the diagnostics collected along the
+/// way. Malformed lines are handled according to `options.mode`.
+pub fn parse_header_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<(BeatmapHeader, Vec<Diagnostic>), ParseError> {
+    read_header(text, false, options)
+}
+
+/// Like [`parse_header_with_counts`], with the same handling of malformed lines as
+/// [`parse_header_with_options`].
+pub fn parse_header_with_counts_and_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<(BeatmapHeader, Vec<Diagnostic>), ParseError> {
+    read_header(text, true, options)
+}
+
+fn read_header(
+    text: &str,
+    count_objects: bool,
+    options: &ParseOptions,
+) -> Result<(BeatmapHeader, Vec<Diagnostic>), ParseError> {
+    let mut beatmap = Beatmap::default();
+    let mut general = GeneralRef::default();
+    let mut metadata = MetadataRef::default();
+    let mut object_counts = ObjectCounts::default();
+    let mut diagnostics = Vec::new();
+    let mut section = FileSections::None;
+
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if FileSections::is_header(line) {
+            let next = FileSections::get_section(line);
+            let done = section == FileSections::Difficulty
+                || matches!(next, FileSections::TimingPoints | FileSections::HitObjects);
+            if done && !count_objects {
+                break;
+            }
+            section = next;
+            if section != FileSections::Format {
+                continue;
+            }
+        }
+
+        let result = match section {
+            FileSections::Format => parse_format_line(line).map(|format| beatmap.format = format),
+            FileSections::General => parse_general_ref(line, &mut general, &beatmap.format),
+            FileSections::Metadata => parse_metadata_ref(line, &mut metadata),
+            FileSections::Difficulty => parse_difficulty(line, &mut beatmap),
+            FileSections::HitObjects if count_objects => count_object(line, &mut object_counts),
+            // nothing else is needed, so `[Events]` and the rest are skipped without being parsed
+            _ => Ok(()),
+        };
+
+        match (result, options.mode) {
+            (Ok(()), _) => {}
+            (Err(e), ParseMode::Strict) => return Err(e.with_line(index + 1)),
+            (Err(e), ParseMode::Lenient) => {
+                diagnostics.push(Diagnostic::from(e.with_line(index + 1)))
+            }
+        }
+    }
+
+    let header = BeatmapHeader {
+        format: beatmap.format,
+        general: general.into_owned(),
+        metadata: metadata.into_owned(),
+        difficulty: beatmap.difficulty,
+        object_counts: count_objects.then_some(object_counts),
+    };
+
+    Ok((header, diagnostics))
+}
+
+/// Counts a hit object by its type column alone, without parsing the rest of the line.
+fn count_object(line: &str, object_counts: &mut ObjectCounts) -> Result<(), ParseError> {
+    let params: Vec<&str> = line.trim().splitn(5, ',').collect();
+    let column = |kind| ParseError::column(FileSections::HitObjects, line, 3, kind);
+
+    match parse_column::<u32>(&params, 3).map_err(column)? & 0b10001011 {
+        1 => object_counts.hit_circles += 1,
+        2 => object_counts.sliders += 1,
+        8 => object_counts.spinners += 1,
+        128 => object_counts.mania_holds += 1,
+        _ => return Err(column(ParseErrorKind::InvalidValue)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::general::Mode;
+
+    const TEST_STR: &str = "osu file format v14
+
+        [General]
+        Mode: 3
+
+        [Metadata]
+        Title:End Time
+        Creator:PaRaDogi
+
+        [Difficulty]
+        OverallDifficulty:8.5
+        ApproachRate:9.8
+
+        [Events]
+        0,0,\"bg.jpg\",0,0
+
+        [TimingPoints]
+        0,500,4,2,1,35,1,0
+
+        [HitObjects]
+        64,192,1000,1,0,0:0:0:0:
+        64,192,1500,1,0,0:0:0:0:
+        192,192,2000,128,0,2500:0:0:0:0:
+        256,192,3000,12,0,4000";
+
+    #[test]
+    fn test_parse_header() {
+        let header = parse_header(TEST_STR).unwrap();
+
+        assert_eq!(header.general.mode, Mode::Mania);
+        assert_eq!(header.metadata.title, String::from("End Time"));
+        assert_eq!(header.metadata.creator, String::from("PaRaDogi"));
+        assert_eq!(header.difficulty.ar, 9.8);
+        assert_eq!(header.object_counts, None);
+    }
+
+    #[test]
+    fn test_parse_header_with_counts() {
+        let header = parse_header_with_counts(TEST_STR).unwrap();
+
+        assert_eq!(
+            header.object_counts,
+            Some(ObjectCounts {
+                hit_circles: 2,
+                sliders: 0,
+                spinners: 1,
+                mania_holds: 1,
+            })
+        );
+        assert_eq!(header.object_counts.unwrap().total(), 4);
+    }
+
+    #[test]
+    fn test_parse_header_lenient() {
+        let test_str = TEST_STR.replace("ApproachRate:9.8", "ApproachRate:fast\nCircleSize:4");
+        let (header, diagnostics) =
+            parse_header_with_options(&test_str, &ParseOptions::new(ParseMode::Lenient)).unwrap();
+
+        assert!(parse_header(&test_str).is_err());
+        assert_eq!(header.difficulty.cs, 4.0);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 12);
+    }
+
+    #[test]
+    fn test_parse_header_skips_events() {
+        let test_str = TEST_STR
+            .replace("0,0,\"bg.jpg\",0,0", "Sprite,Nowhere,Centre")
+            .replace("256,192,3000,12,0,4000", "256,192,3000,2,0,Q|bad");
+
+        assert_eq!(parse_header(&test_str), parse_header(TEST_STR));
+        let header = parse_header_with_counts(&test_str).unwrap();
+        assert_eq!(header.object_counts.unwrap().sliders, 1);
+        assert!(crate::parse(&test_str).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{events::Event, hit_objects::HitObject, time::Time, Beatmap};
+
+/// The health osu!lazer gives back for the best result of a judgement that isn't a bonus.
+const MAX_HEALTH_INCREASE: f64 = 0.05;
+
+/// The health drained per millisecond, from the HP of `beatmap` and a simulated full combo.
+///
+/// Every judgement gives back the most health it can: circles, spinners and mania notes when
+/// they're hit, and a slider at its head, ticks, repeats, tail and once more at its end. Spinner
+/// bonus spins aren't counted. Health starts draining at the first object.
+pub fn resolve_drain_rate(beatmap: &Beatmap) -> f64 {
+    let timing = beatmap.timing();
+    let difficulty = &beatmap.difficulty;
+
+    let mut judgements: Vec<(Time, f64)> = Vec::new();
+    for hit_object in &beatmap.hit_objects {
+        match hit_object {
+            HitObject::HitCircle(t) => judgements.push((t.time, MAX_HEALTH_INCREASE)),
+            HitObject::Slider(t) => {
+                let nested_objects = t.nested_objects(&timing, difficulty, &beatmap.format);
+                judgements.extend(nested_objects.iter().map(|t| (t.time, MAX_HEALTH_INCREASE)));
+                judgements.push((t.end_time(&timing, difficulty), MAX_HEALTH_INCREASE));
+            }
+            HitObject::Spinner(t) => judgements.push((t.end_time, MAX_HEALTH_INCREASE)),
+            HitObject::ManiaHold(t) => {
+                judgements.push((t.time, MAX_HEALTH_INCREASE));
+                judgements.push((t.end_time, MAX_HEALTH_INCREASE));
+            }
+        }
+    }
+    judgements.sort_by(|a, b| a.0.as_ms().total_cmp(&b.0.as_ms()));
+
+    let mut breaks: Vec<_> = beatmap
+        .events
+        .iter()
+        .filter_map(|t| match t {
+            Event::Break(t) => Some(t.clone()),
+            _ => None,
+        })
+        .collect();
+    breaks.sort_by(|a, b| a.end_time.as_ms().total_cmp(&b.end_time.as_ms()));
+
+    let drain_start = beatmap
+        .hit_objects
+        .first()
+        .map_or(Time::ZERO, HitObject::time);
+    difficulty.drain_rate(drain_start, &judgements, &breaks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::Break, Difficulty};
+
+    #[test]
+    fn test_drain_rate() {
+        let difficulty = Difficulty::default();
+        let judgements = [
+            (Time::from(1000), MAX_HEALTH_INCREASE),
+            (Time::from(2000), MAX_HEALTH_INCREASE),
+        ];
+        let breaks = [Break {
+            start_time: Time::from(1200),
+            end_time: Time::from(1800),
+        }];
+
+        // the lowest health is reached at the second hit, 1.05 - 2000 * rate, aiming at 0.7
+        let rate = difficulty.drain_rate(Time::ZERO, &judgements, &[]);
+        assert!((rate - 0.35 / 2000.0).abs() < 0.01 / 2000.0);
+        // without drain across the break, it's reached at the first hit instead
+        let rate = difficulty.drain_rate(Time::ZERO, &judgements, &breaks);
+        assert!((rate - 0.3 / 1000.0).abs() < 0.01 / 1000.0);
+        assert_eq!(
+            difficulty.drain_rate(Time::ZERO, &judgements[..1], &[]),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_resolve_drain_rate() {
+        let test_str = "osu file format v14
+
+            [Difficulty]
+            HPDrainRate:2
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+
+            [HitObjects]
+            256,192,1000,1,0
+            256,192,2000,2,0,L|356:192,1,100
+            256,192,4000,12,0,6000
+            256,192,8000,1,0";
+        let mut beatmap = crate::parse(test_str).unwrap();
+        let lenient = resolve_drain_rate(&beatmap);
+        beatmap.difficulty.hp = 8.0;
+        let harsh = resolve_drain_rate(&beatmap);
+
+        assert!(lenient > 0.0);
+        assert!(harsh > lenient);
+    }
+}
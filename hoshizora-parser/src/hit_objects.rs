@@ -1,42 +1,126 @@
-use crate::Beatmap;
-
-#[derive(Debug, PartialEq)]
-pub enum HitObject {
-    HitCircle(HitCircle),
-    Slider(Slider),
-    Spinner(Spinner),
-    ManiaHold(ManiaHold),
+use crate::{
+    difficulty::Difficulty,
+    error::{default_if_missing, parse_column, ParseError, ParseErrorKind},
+    file_sections::FileSections,
+    format::Format,
+    general::SampleSet,
+    time::Time,
+    timing_points::TimingModel,
+    Beatmap,
+};
+use bitflags::bitflags;
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A hit object. `S` is the type of sample filenames, `&str` when they're borrowed from the
+/// parsed text by [`parse_hit_object_ref`].
+pub enum HitObject<S = String> {
+    HitCircle(HitCircle<S>),
+    Slider(Slider<S>),
+    Spinner(Spinner<S>),
+    ManiaHold(ManiaHold<S>),
+}
+
+bitflags! {
+    /// Additions played on a hit object or a slider edge. The normal sound always plays, even
+    /// when [`Hitsound::NORMAL`] isn't set.
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Hitsound: u8 {
+        const NORMAL = 1;
+        const WHISTLE = 1 << 1;
+        const FINISH = 1 << 2;
+        const CLAP = 1 << 3;
+    }
+}
+
+impl Hitsound {
+    pub fn has_normal(&self) -> bool {
+        self.contains(Self::NORMAL)
+    }
+
+    pub fn has_whistle(&self) -> bool {
+        self.contains(Self::WHISTLE)
+    }
+
+    pub fn has_finish(&self) -> bool {
+        self.contains(Self::FINISH)
+    }
+
+    pub fn has_clap(&self) -> bool {
+        self.contains(Self::CLAP)
+    }
+}
+
+impl FromStr for Hitsound {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse::<u8>().map(Self::from_bits_retain)
+    }
+}
+
+impl fmt::Display for Hitsound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bits())
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct HitCircle {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitCircle<S = String> {
     pub position_x: i32,
     pub position_y: i32,
     pub new_combo: bool,
     pub color_skip: u32,
-    pub time: u32,
-    pub hitsound: u32,
-    pub hit_sample: HitSample,
+    pub time: Time,
+    pub hitsound: Hitsound,
+    pub hit_sample: HitSample<S>,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Slider {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Slider<S = String> {
     pub position_x: i32,
     pub position_y: i32,
     pub new_combo: bool,
     pub color_skip: u32,
-    pub time: u32,
-    pub hitsound: u32,
+    pub time: Time,
+    pub hitsound: Hitsound,
     pub curve_type: CurveType,
     pub curve_points: Vec<(i32, i32)>,
     pub slides: u32,
-    pub length: f32,
-    pub edge_sounds: Vec<u32>,
-    pub edge_additions: Vec<(u32, u32)>,
-    pub hit_sample: HitSample,
+    pub length: f64,
+    pub edge_sounds: Vec<Hitsound>,
+    /// Normal and addition sample sets of each edge.
+    pub edge_additions: Vec<(SampleSet, SampleSet)>,
+    pub hit_sample: HitSample<S>,
 }
 
-#[derive(Debug, PartialEq)]
+impl<S> Slider<S> {
+    /// The time the slider takes to travel its length once: the slider multiplier and the
+    /// inherited point's velocity give the osu!pixels it travels per beat of the uninherited
+    /// point.
+    pub fn span_duration(&self, timing: &TimingModel, difficulty: &Difficulty) -> Time {
+        let velocity =
+            100.0 * difficulty.effective_slider_multiplier() * timing.slider_velocity_at(self.time);
+
+        Time::from_ms(self.length / velocity * timing.beat_length_at(self.time))
+    }
+
+    /// The time from the head to the end of the last slide.
+    pub fn duration(&self, timing: &TimingModel, difficulty: &Difficulty) -> Time {
+        self.span_duration(timing, difficulty) * f64::from(self.slides.max(1))
+    }
+
+    pub fn end_time(&self, timing: &TimingModel, difficulty: &Difficulty) -> Time {
+        self.time + self.duration(timing, difficulty)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CurveType {
     Linear,
     Bezier,
@@ -44,51 +128,265 @@ pub enum CurveType {
     Catmull,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Spinner {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spinner<S = String> {
     pub position_x: i32,
     pub position_y: i32,
     pub new_combo: bool,
     pub color_skip: u32,
-    pub time: u32,
-    pub hitsound: u32,
-    pub end_time: u32,
-    pub hit_sample: HitSample,
+    pub time: Time,
+    pub hitsound: Hitsound,
+    pub end_time: Time,
+    pub hit_sample: HitSample<S>,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct ManiaHold {
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManiaHold<S = String> {
     pub position_x: i32,
     pub position_y: i32,
     pub new_combo: bool,
     pub color_skip: u32,
-    pub time: u32,
-    pub hitsound: u32,
-    pub end_time: u32,
-    pub hit_sample: HitSample,
+    pub time: Time,
+    pub hitsound: Hitsound,
+    pub end_time: Time,
+    pub hit_sample: HitSample<S>,
 }
 
-#[derive(Default, Debug, PartialEq)]
-pub struct HitSample {
-    pub normal_set: u32,
-    pub addition_set: u32,
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitSample<S = String> {
+    pub normal_set: SampleSet,
+    pub addition_set: SampleSet,
     pub index: u32,
     pub volume: u32,
-    pub filename: String,
+    pub filename: S,
+}
+
+impl HitSample<&str> {
+    pub fn into_owned(self) -> HitSample {
+        HitSample {
+            normal_set: self.normal_set,
+            addition_set: self.addition_set,
+            index: self.index,
+            volume: self.volume,
+            filename: String::from(self.filename),
+        }
+    }
+}
+
+impl HitObject<&str> {
+    pub fn into_owned(self) -> HitObject {
+        match self {
+            HitObject::HitCircle(t) => HitObject::HitCircle(HitCircle {
+                position_x: t.position_x,
+                position_y: t.position_y,
+                new_combo: t.new_combo,
+                color_skip: t.color_skip,
+                time: t.time,
+                hitsound: t.hitsound,
+                hit_sample: t.hit_sample.into_owned(),
+            }),
+            HitObject::Slider(t) => HitObject::Slider(Slider {
+                position_x: t.position_x,
+                position_y: t.position_y,
+                new_combo: t.new_combo,
+                color_skip: t.color_skip,
+                time: t.time,
+                hitsound: t.hitsound,
+                curve_type: t.curve_type,
+                curve_points: t.curve_points,
+                slides: t.slides,
+                length: t.length,
+                edge_sounds: t.edge_sounds,
+                edge_additions: t.edge_additions,
+                hit_sample: t.hit_sample.into_owned(),
+            }),
+            HitObject::Spinner(t) => HitObject::Spinner(Spinner {
+                position_x: t.position_x,
+                position_y: t.position_y,
+                new_combo: t.new_combo,
+                color_skip: t.color_skip,
+                time: t.time,
+                hitsound: t.hitsound,
+                end_time: t.end_time,
+                hit_sample: t.hit_sample.into_owned(),
+            }),
+            HitObject::ManiaHold(t) => HitObject::ManiaHold(ManiaHold {
+                position_x: t.position_x,
+                position_y: t.position_y,
+                new_combo: t.new_combo,
+                color_skip: t.color_skip,
+                time: t.time,
+                hitsound: t.hitsound,
+                end_time: t.end_time,
+                hit_sample: t.hit_sample.into_owned(),
+            }),
+        }
+    }
+}
+
+impl<S> HitObject<S> {
+    pub fn time(&self) -> Time {
+        match self {
+            HitObject::HitCircle(t) => t.time,
+            HitObject::Slider(t) => t.time,
+            HitObject::Spinner(t) => t.time,
+            HitObject::ManiaHold(t) => t.time,
+        }
+    }
+
+    /// When the object stops needing input: its time for a hit circle, and the end of its last
+    /// slide, spin or hold otherwise. Only sliders need the timing and difficulty to know it.
+    pub fn end_time(&self, timing: &TimingModel, difficulty: &Difficulty) -> Time {
+        match self {
+            HitObject::HitCircle(t) => t.time,
+            HitObject::Slider(t) => t.end_time(timing, difficulty),
+            HitObject::Spinner(t) => t.end_time,
+            HitObject::ManiaHold(t) => t.end_time,
+        }
+    }
+}
+
+impl<S: fmt::Display + Default + PartialEq> fmt::Display for HitObject<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let object_type = |base: u32, new_combo: bool, color_skip: u32| {
+            base | u32::from(new_combo) << 2 | (color_skip & 0b00000111) << 4
+        };
+
+        match self {
+            HitObject::HitCircle(t) => write!(
+                f,
+                "{},{},{},{},{},{}",
+                t.position_x,
+                t.position_y,
+                t.time,
+                object_type(1, t.new_combo, t.color_skip),
+                t.hitsound,
+                t.hit_sample
+            ),
+            HitObject::Slider(t) => {
+                write!(
+                    f,
+                    "{},{},{},{},{},{}",
+                    t.position_x,
+                    t.position_y,
+                    t.time,
+                    object_type(2, t.new_combo, t.color_skip),
+                    t.hitsound,
+                    t.curve_type
+                )?;
+                for (x, y) in &t.curve_points {
+                    write!(f, "|{}:{}", x, y)?;
+                }
+                write!(f, ",{},{}", t.slides, t.length)?;
+
+                // the edge columns are optional, but can't be skipped if a hit sample follows
+                if t.edge_sounds.is_empty()
+                    && t.edge_additions.is_empty()
+                    && t.hit_sample == HitSample::default()
+                {
+                    return Ok(());
+                }
+                let edges = t.slides as usize + 1;
+                let edge_sounds: Vec<String> = match t.edge_sounds.is_empty() {
+                    true => vec![String::from("0"); edges],
+                    false => t.edge_sounds.iter().map(|s| s.to_string()).collect(),
+                };
+                let edge_additions: Vec<String> = match t.edge_additions.is_empty() {
+                    true => vec![String::from("0:0"); edges],
+                    false => t
+                        .edge_additions
+                        .iter()
+                        .map(|(normal, addition)| format!("{}:{}", *normal as u8, *addition as u8))
+                        .collect(),
+                };
+                write!(
+                    f,
+                    ",{},{},{}",
+                    edge_sounds.join("|"),
+                    edge_additions.join("|"),
+                    t.hit_sample
+                )
+            }
+            HitObject::Spinner(t) => write!(
+                f,
+                "{},{},{},{},{},{},{}",
+                t.position_x,
+                t.position_y,
+                t.time,
+                object_type(8, t.new_combo, t.color_skip),
+                t.hitsound,
+                t.end_time,
+                t.hit_sample
+            ),
+            HitObject::ManiaHold(t) => write!(
+                f,
+                "{},{},{},{},{},{}:{}",
+                t.position_x,
+                t.position_y,
+                t.time,
+                object_type(128, t.new_combo, t.color_skip),
+                t.hitsound,
+                t.end_time,
+                t.hit_sample
+            ),
+        }
+    }
 }
 
-pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) {
-    let mut iter = line.trim().split(',');
+impl fmt::Display for CurveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveType::Bezier => write!(f, "B"),
+            CurveType::Catmull => write!(f, "C"),
+            CurveType::Linear => write!(f, "L"),
+            CurveType::Perfect => write!(f, "P"),
+        }
+    }
+}
+
+impl<S: fmt::Display> fmt::Display for HitSample<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}:{}",
+            self.normal_set as u8, self.addition_set as u8, self.index, self.volume, self.filename
+        )
+    }
+}
 
-    let position_x = iter.next().unwrap().parse::<i32>().unwrap();
-    let position_y = iter.next().unwrap().parse::<i32>().unwrap();
-    let time = iter.next().unwrap().parse::<u32>().unwrap();
-    let object_type = iter.next().unwrap().parse::<u32>().unwrap();
+pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let hit_object = parse_hit_object(line, &beatmap.format)?;
+    beatmap.hit_objects.push(hit_object);
+
+    Ok(())
+}
+
+pub fn parse_hit_object(line: &str, format: &Format) -> Result<HitObject, ParseError> {
+    parse_hit_object_ref(line, format).map(HitObject::into_owned)
+}
+
+/// Like [`parse_hit_object`], without copying the sample filename out of `line`.
+pub fn parse_hit_object_ref<'a>(
+    line: &'a str,
+    format: &Format,
+) -> Result<HitObject<&'a str>, ParseError> {
+    let params: Vec<&str> = line.trim().split(',').collect();
+    let column =
+        |index| move |kind| ParseError::column(FileSections::HitObjects, line, index, kind);
+
+    let position_x = parse_column::<i32>(&params, 0).map_err(column(0))?;
+    let position_y = parse_column::<i32>(&params, 1).map_err(column(1))?;
+    let offset = format.offset();
+    let time = Time::from(parse_column::<Time>(&params, 2).map_err(column(2))?.round()) + offset;
+    let object_type = parse_column::<u32>(&params, 3).map_err(column(3))?;
 
     let new_combo = object_type & 0b00000100 != 0;
     let color_skip = (object_type >> 4) & 0b00000111;
 
-    let hitsound = iter.next().unwrap().parse::<u32>().unwrap();
+    let hitsound = parse_column::<Hitsound>(&params, 4).map_err(column(4))?;
 
     let hit_object = match object_type & 0b10001011 {
         1 => HitObject::HitCircle(HitCircle {
@@ -98,17 +396,20 @@ pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) {
             color_skip,
             time,
             hitsound,
-            hit_sample: match iter.next() {
-                Some(t) => parse_hit_sample(t),
+            hit_sample: match params.get(5) {
+                Some(t) => parse_hit_sample(t).map_err(column(5))?,
                 None => HitSample::default(),
             },
         }),
 
         2 => {
-            let (curve_type, curve_points) = match iter.next() {
-                Some(t) => parse_curve(t),
-                None => panic!(),
-            };
+            let (curve_type, curve_points) = parse_curve(
+                params
+                    .get(5)
+                    .ok_or(ParseErrorKind::MissingField)
+                    .map_err(column(5))?,
+            )
+            .map_err(column(5))?;
 
             HitObject::Slider(Slider {
                 position_x,
@@ -119,18 +420,18 @@ pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) {
                 hitsound,
                 curve_type,
                 curve_points,
-                slides: iter.next().unwrap().parse::<u32>().unwrap(),
-                length: iter.next().unwrap().parse::<f32>().unwrap(),
-                edge_sounds: match iter.next() {
-                    Some(t) => parse_slider_edge_sounds(t),
-                    None => panic!(),
+                slides: parse_column(&params, 6).map_err(column(6))?,
+                length: parse_column(&params, 7).map_err(column(7))?,
+                edge_sounds: match params.get(8) {
+                    Some(t) => parse_slider_edge_sounds(t).map_err(column(8))?,
+                    None => Vec::new(),
                 },
-                edge_additions: match iter.next() {
-                    Some(t) => parse_slider_edge_sets(t),
-                    None => panic!(),
+                edge_additions: match params.get(9) {
+                    Some(t) => parse_slider_edge_sets(t).map_err(column(9))?,
+                    None => Vec::new(),
                 },
-                hit_sample: match iter.next() {
-                    Some(t) => parse_hit_sample(t),
+                hit_sample: match params.get(10) {
+                    Some(t) => parse_hit_sample(t).map_err(column(10))?,
                     None => HitSample::default(),
                 },
             })
@@ -143,24 +444,32 @@ pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) {
             color_skip,
             time,
             hitsound,
-            end_time: iter.next().unwrap().parse::<u32>().unwrap(),
-            hit_sample: match iter.next() {
-                Some(t) => parse_hit_sample(t),
+            end_time: Time::from(parse_column::<Time>(&params, 5).map_err(column(5))?.round())
+                + offset,
+            hit_sample: match params.get(6) {
+                Some(t) => parse_hit_sample(t).map_err(column(6))?,
                 None => HitSample::default(),
             },
         }),
 
         128 => {
-            let (end_time, hit_sample) = iter
-                .next()
-                .unwrap()
-                .split_once(':')
-                .map(|(et, hs)| {
-                    let et = et.parse::<u32>().unwrap();
-                    let hs = parse_hit_sample(hs);
-                    (et, hs)
+            let (end_time, hit_sample) = params
+                .get(5)
+                .ok_or(ParseErrorKind::MissingField)
+                .and_then(|t| match t.split_once(':') {
+                    Some((et, hs)) => Ok((
+                        et.parse::<Time>()
+                            .map_err(|_| ParseErrorKind::InvalidValue)?,
+                        parse_hit_sample(hs)?,
+                    )),
+                    None => Ok((
+                        t.parse::<Time>()
+                            .map_err(|_| ParseErrorKind::InvalidValue)?,
+                        HitSample::default(),
+                    )),
                 })
-                .unwrap();
+                .map_err(column(5))?;
+
             HitObject::ManiaHold(ManiaHold {
                 position_x,
                 position_y,
@@ -168,67 +477,72 @@ pub fn parse_hit_objects(line: &str, beatmap: &mut Beatmap) {
                 color_skip,
                 time,
                 hitsound,
-                end_time,
+                end_time: Time::from(end_time.round()) + offset,
                 hit_sample,
             })
         }
-        _ => unimplemented!(),
+        _ => return Err(column(3)(ParseErrorKind::InvalidValue)),
     };
 
-    beatmap.hit_objects.push(hit_object);
+    Ok(hit_object)
 }
 
-pub fn parse_hit_sample(line: &str) -> HitSample {
-    let mut iter = line.trim().split(':');
-
-    let normal_set = iter.next().unwrap().parse::<u32>().unwrap();
-    let addition_set = iter.next().unwrap().parse::<u32>().unwrap();
-    let index = iter.next().unwrap().parse::<u32>().unwrap();
-    let volume = iter.next().unwrap().parse::<u32>().unwrap();
-    let filename = iter.next().unwrap().to_string();
-
-    HitSample {
-        normal_set,
-        addition_set,
-        index,
-        volume,
-        filename,
-    }
+pub fn parse_hit_sample(line: &str) -> Result<HitSample<&str>, ParseErrorKind> {
+    let params: Vec<&str> = line.trim().split(':').collect();
+
+    Ok(HitSample {
+        normal_set: parse_column(&params, 0)?,
+        addition_set: parse_column(&params, 1)?,
+        index: parse_column(&params, 2).or_else(default_if_missing)?,
+        volume: parse_column(&params, 3).or_else(default_if_missing)?,
+        filename: params.get(4).copied().unwrap_or_default(),
+    })
 }
 
-pub fn parse_curve(line: &str) -> (CurveType, Vec<(i32, i32)>) {
+pub fn parse_curve(line: &str) -> Result<(CurveType, Vec<(i32, i32)>), ParseErrorKind> {
     let mut iter = line.trim().split('|');
 
-    let curve_type = match iter.next().unwrap() {
-        "B" => CurveType::Bezier,
-        "C" => CurveType::Catmull,
-        "L" => CurveType::Linear,
-        "P" => CurveType::Perfect,
-        _ => panic!(),
+    let curve_type = match iter.next() {
+        Some("B") => CurveType::Bezier,
+        Some("C") => CurveType::Catmull,
+        Some("L") => CurveType::Linear,
+        Some("P") => CurveType::Perfect,
+        _ => return Err(ParseErrorKind::InvalidValue),
     };
 
     let mut curve_points: Vec<(i32, i32)> = vec![];
     for t in iter {
-        let (x, y) = t.split_once(':').unwrap();
-        curve_points.push((x.parse::<i32>().unwrap(), y.parse::<i32>().unwrap()));
+        let (x, y) = t.split_once(':').ok_or(ParseErrorKind::MissingField)?;
+        curve_points.push((
+            x.parse::<i32>().map_err(|_| ParseErrorKind::InvalidValue)?,
+            y.parse::<i32>().map_err(|_| ParseErrorKind::InvalidValue)?,
+        ));
     }
 
-    (curve_type, curve_points)
+    Ok((curve_type, curve_points))
 }
 
-pub fn parse_slider_edge_sounds(line: &str) -> Vec<u32> {
+pub fn parse_slider_edge_sounds(line: &str) -> Result<Vec<Hitsound>, ParseErrorKind> {
     line.trim()
         .split('|')
-        .map(|s| s.parse::<u32>().unwrap())
+        .map(|s| {
+            s.parse::<Hitsound>()
+                .map_err(|_| ParseErrorKind::InvalidValue)
+        })
         .collect()
 }
 
-pub fn parse_slider_edge_sets(line: &str) -> Vec<(u32, u32)> {
+pub fn parse_slider_edge_sets(line: &str) -> Result<Vec<(SampleSet, SampleSet)>, ParseErrorKind> {
     line.trim()
         .split('|')
         .map(|s| {
-            let (x, y) = s.split_once(':').unwrap();
-            (x.parse::<u32>().unwrap(), y.parse::<u32>().unwrap())
+            let (x, y) = s.split_once(':').ok_or(ParseErrorKind::MissingField)?;
+            Ok((
+                x.parse::<SampleSet>()
+                    .map_err(|_| ParseErrorKind::InvalidValue)?,
+                y.parse::<SampleSet>()
+                    .map_err(|_| ParseErrorKind::InvalidValue)?,
+            ))
         })
         .collect()
 }
@@ -244,7 +558,7 @@ mod tests {
         100,100,12600,6,1,B|200:200|250:200|250:200|300:150,2,310.123,2|1|2,0:0|0:0|0:2,0:0:0:0:";
         let mut beatmap = Beatmap::default();
         for line in test_str.lines() {
-            parse_hit_objects(line, &mut beatmap);
+            parse_hit_objects(line, &mut beatmap).unwrap();
         }
 
         assert_eq!(
@@ -254,8 +568,8 @@ mod tests {
                 position_y: 192,
                 new_combo: true,
                 color_skip: 1,
-                time: 11000,
-                hitsound: 2,
+                time: Time::from(11000),
+                hitsound: Hitsound::WHISTLE,
                 hit_sample: HitSample::default(),
             })
         );
@@ -267,12 +581,12 @@ mod tests {
                 position_y: 192,
                 new_combo: false,
                 color_skip: 0,
-                time: 11200,
-                hitsound: 12,
-                end_time: 12000,
+                time: Time::from(11200),
+                hitsound: Hitsound::FINISH | Hitsound::CLAP,
+                end_time: Time::from(12000),
                 hit_sample: HitSample {
-                    normal_set: 3,
-                    addition_set: 0,
+                    normal_set: SampleSet::Drum,
+                    addition_set: SampleSet::Default,
                     index: 0,
                     volume: 80,
                     filename: String::new(),
@@ -287,16 +601,173 @@ mod tests {
                 position_y: 100,
                 new_combo: true,
                 color_skip: 0,
-                time: 12600,
-                hitsound: 1,
+                time: Time::from(12600),
+                hitsound: Hitsound::NORMAL,
                 curve_type: CurveType::Bezier,
                 curve_points: vec![(200, 200), (250, 200), (250, 200), (300, 150)],
                 slides: 2,
                 length: 310.123,
-                edge_sounds: vec![2, 1, 2],
-                edge_additions: vec![(0, 0), (0, 0), (0, 2)],
+                edge_sounds: vec![Hitsound::WHISTLE, Hitsound::NORMAL, Hitsound::WHISTLE],
+                edge_additions: vec![
+                    (SampleSet::Default, SampleSet::Default),
+                    (SampleSet::Default, SampleSet::Default),
+                    (SampleSet::Default, SampleSet::Soft),
+                ],
                 hit_sample: HitSample::default(),
             })
         );
     }
+
+    #[test]
+    fn test_parse_hitsound() {
+        let hitsound = "10".parse::<Hitsound>().unwrap();
+
+        assert!(hitsound.has_whistle());
+        assert!(hitsound.has_clap());
+        assert!(!hitsound.has_normal());
+        assert!(!hitsound.has_finish());
+        assert_eq!(hitsound.to_string(), "10");
+    }
+
+    #[test]
+    fn test_parse_legacy_hit_objects() {
+        let test_str = "64,80,1000,1,0
+            256,192,2000,12,0,3000";
+        let mut beatmap = Beatmap {
+            format: Format::new(3),
+            ..Beatmap::default()
+        };
+        for line in test_str.lines() {
+            parse_hit_objects(line, &mut beatmap).unwrap();
+        }
+
+        assert_eq!(
+            beatmap.hit_objects,
+            vec![
+                HitObject::HitCircle(HitCircle {
+                    position_x: 64,
+                    position_y: 80,
+                    new_combo: false,
+                    color_skip: 0,
+                    time: Time::from(1024),
+                    hitsound: Hitsound::empty(),
+                    hit_sample: HitSample::default(),
+                }),
+                HitObject::Spinner(Spinner {
+                    position_x: 256,
+                    position_y: 192,
+                    new_combo: true,
+                    color_skip: 0,
+                    time: Time::from(2024),
+                    hitsound: Hitsound::empty(),
+                    end_time: Time::from(3024),
+                    hit_sample: HitSample::default(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fractional_time() {
+        let test_str = "64,80,1000.5,1,0
+            64,80,1001.5,1,0
+            256,192,2000.4,12,0,2999.6";
+        let mut beatmap = Beatmap::default();
+        for line in test_str.lines() {
+            parse_hit_objects(line, &mut beatmap).unwrap();
+        }
+        let times: Vec<Time> = beatmap.hit_objects.iter().map(|t| t.time()).collect();
+
+        assert_eq!(
+            times,
+            vec![Time::from(1000), Time::from(1002), Time::from(2000)]
+        );
+        match &beatmap.hit_objects[2] {
+            HitObject::Spinner(t) => assert_eq!(t.end_time, Time::from(3000)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_end_time() {
+        let test_str = "osu file format v14
+
+            [Difficulty]
+            SliderMultiplier:1.4
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+            1500,-50,4,2,1,60,0,0
+
+            [HitObjects]
+            0,0,0,2,0,L|140:0,2,140
+            0,0,2000,2,0,L|280:0,1,280
+            256,192,3000,12,0,4000
+            256,192,5000,1,0";
+        let beatmap = crate::parse(test_str).unwrap();
+        let timing = beatmap.timing();
+        let end_times: Vec<Time> = beatmap
+            .hit_objects
+            .iter()
+            .map(|t| t.end_time(&timing, &beatmap.difficulty))
+            .collect();
+
+        assert_eq!(
+            end_times,
+            vec![
+                Time::from(1000),
+                Time::from(2500),
+                Time::from(4000),
+                Time::from(5000)
+            ]
+        );
+        match &beatmap.hit_objects[1] {
+            HitObject::Slider(t) => {
+                assert_eq!(
+                    t.span_duration(&timing, &beatmap.difficulty),
+                    Time::from(500)
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_end_time_default_difficulty() {
+        // without [Difficulty], sliders move at the game's default 1.4x multiplier
+        let test_str = "osu file format v14
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+
+            [HitObjects]
+            0,0,0,2,0,L|140:0,1,140";
+        let beatmap = crate::parse(test_str).unwrap();
+
+        assert_eq!(
+            beatmap.hit_objects[0].end_time(&beatmap.timing(), &beatmap.difficulty),
+            Time::from(500)
+        );
+    }
+
+    #[test]
+    fn test_end_time_zero_multiplier() {
+        // the multiplier is clamped to 0.4x, and a slider with no slides still runs once
+        let test_str = "osu file format v14
+
+            [Difficulty]
+            SliderMultiplier:0
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+
+            [HitObjects]
+            0,0,0,2,0,L|40:0,0,40";
+        let beatmap = crate::parse(test_str).unwrap();
+
+        assert_eq!(
+            beatmap.hit_objects[0].end_time(&beatmap.timing(), &beatmap.difficulty),
+            Time::from(500)
+        );
+    }
 }
@@ -0,0 +1,256 @@
+use crate::{
+    general::SampleSet,
+    hit_objects::{HitObject, HitSample, Hitsound},
+    time::Time,
+    timing_points::{TimingModel, TimingPoint},
+    Beatmap,
+};
+
+/// How late a timing point may start and still apply to a sample, in milliseconds. Mappers
+/// often place hitsound changes a little after the object they're meant for.
+const SAMPLE_LENIENCY: Time = Time::from_ms(5.0);
+
+/// A sample file played by a hit object.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitsoundSample {
+    /// The file to play, e.g. `soft-hitclap2.wav`.
+    pub filename: String,
+    /// The default skin's sample played instead when `filename` uses a custom sample index and
+    /// the beatmap folder doesn't contain it.
+    pub fallback: Option<String>,
+    pub volume: u32,
+}
+
+/// The samples played together at one point of a hit object: the hit of a circle, each edge of a
+/// slider, or the end of a spinner.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HitsoundEvent {
+    pub time: Time,
+    pub samples: Vec<HitsoundSample>,
+}
+
+/// Resolves the samples played by every hit object, in the order of `beatmap.hit_objects`.
+pub fn resolve_hitsounds(beatmap: &Beatmap) -> Vec<Vec<HitsoundEvent>> {
+    let timing = beatmap.timing();
+    beatmap
+        .hit_objects
+        .iter()
+        .map(|hit_object| match hit_object {
+            HitObject::HitCircle(t) => vec![resolve_event(
+                beatmap,
+                &timing,
+                t.time,
+                t.hitsound,
+                (t.hit_sample.normal_set, t.hit_sample.addition_set),
+                &t.hit_sample,
+            )],
+            HitObject::Slider(t) => {
+                let span_duration = t.span_duration(&timing, &beatmap.difficulty);
+                (0..=t.slides as usize)
+                    .map(|edge| {
+                        resolve_event(
+                            beatmap,
+                            &timing,
+                            t.time + span_duration * edge as f64,
+                            t.edge_sounds.get(edge).copied().unwrap_or(t.hitsound),
+                            t.edge_additions
+                                .get(edge)
+                                .copied()
+                                .unwrap_or((t.hit_sample.normal_set, t.hit_sample.addition_set)),
+                            &t.hit_sample,
+                        )
+                    })
+                    .collect()
+            }
+            HitObject::Spinner(t) => vec![resolve_event(
+                beatmap,
+                &timing,
+                t.end_time,
+                t.hitsound,
+                (t.hit_sample.normal_set, t.hit_sample.addition_set),
+                &t.hit_sample,
+            )],
+            HitObject::ManiaHold(t) => vec![resolve_event(
+                beatmap,
+                &timing,
+                t.time,
+                t.hitsound,
+                (t.hit_sample.normal_set, t.hit_sample.addition_set),
+                &t.hit_sample,
+            )],
+        })
+        .collect()
+}
+
+/// Applies the fallback chain: the hit sample's filename replaces every sound, then each of the
+/// sets, index and volume falls back from the object to the timing point, and the sets finally
+/// to `[General]`.
+fn resolve_event(
+    beatmap: &Beatmap,
+    timing: &TimingModel,
+    time: Time,
+    hitsound: Hitsound,
+    (normal_set, addition_set): (SampleSet, SampleSet),
+    hit_sample: &HitSample,
+) -> HitsoundEvent {
+    let default_timing_point = TimingPoint::default();
+    let timing_point = timing
+        .timing_point_at(time + SAMPLE_LENIENCY)
+        .unwrap_or(&default_timing_point);
+
+    let volume = match hit_sample.volume {
+        0 => timing_point.volume,
+        t => t,
+    };
+    if !hit_sample.filename.is_empty() {
+        return HitsoundEvent {
+            time,
+            samples: vec![HitsoundSample {
+                filename: hit_sample.filename.clone(),
+                fallback: None,
+                volume,
+            }],
+        };
+    }
+
+    let normal_set = [
+        normal_set,
+        timing_point.sample_set,
+        beatmap.general.sample_set,
+    ]
+    .into_iter()
+    .find(|t| *t != SampleSet::Default)
+    .unwrap_or(SampleSet::Normal);
+    let addition_set = match addition_set {
+        SampleSet::Default => normal_set,
+        t => t,
+    };
+    let index = match hit_sample.index {
+        0 => timing_point.sample_index,
+        t => t,
+    };
+
+    // the normal sound plays whether or not its bit is set
+    let sounds = [
+        (true, normal_set, "normal"),
+        (hitsound.has_whistle(), addition_set, "whistle"),
+        (hitsound.has_finish(), addition_set, "finish"),
+        (hitsound.has_clap(), addition_set, "clap"),
+    ];
+    let samples = sounds
+        .into_iter()
+        .filter(|(played, _, _)| *played)
+        .map(|(_, set, sound)| sample(set, sound, index, volume))
+        .collect();
+
+    HitsoundEvent { time, samples }
+}
+
+/// Index 0 uses the skin's samples, 1 the beatmap's samples without a suffix, and anything higher
+/// the beatmap's samples suffixed with the index.
+fn sample(set: SampleSet, sound: &str, index: u32, volume: u32) -> HitsoundSample {
+    let set = match set {
+        SampleSet::Soft => "soft",
+        SampleSet::Drum => "drum",
+        SampleSet::Normal | SampleSet::Default => "normal",
+    };
+    let skin_filename = format!("{}-hit{}.wav", set, sound);
+
+    let (filename, fallback) = match index {
+        0 => (skin_filename, None),
+        1 => (skin_filename.clone(), Some(skin_filename)),
+        t => (
+            format!("{}-hit{}{}.wav", set, sound, t),
+            Some(skin_filename),
+        ),
+    };
+
+    HitsoundSample {
+        filename,
+        fallback,
+        volume,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn filenames(event: &HitsoundEvent) -> Vec<&str> {
+        event.samples.iter().map(|t| t.filename.as_str()).collect()
+    }
+
+    #[test]
+    fn test_resolve_hitsounds() {
+        let test_str = "osu file format v14
+
+            [General]
+            SampleSet: Soft
+
+            [Difficulty]
+            SliderMultiplier:1
+
+            [TimingPoints]
+            0,500,4,0,0,60,1,0
+            1003,-100,4,3,2,80,0,0
+
+            [HitObjects]
+            256,192,0,1,8,0:0:0:0:
+            256,192,1000,1,2,0:2:0:0:
+            256,192,2000,5,0,0:0:0:40:custom.wav
+            100,100,3000,2,0,L|200:100,1,100,2|8,1:0|0:1,0:0:5:0:";
+        let beatmap = parse(test_str).unwrap();
+        let hitsounds = resolve_hitsounds(&beatmap);
+
+        assert_eq!(
+            hitsounds[0],
+            vec![HitsoundEvent {
+                time: Time::ZERO,
+                samples: vec![
+                    HitsoundSample {
+                        filename: String::from("soft-hitnormal.wav"),
+                        fallback: None,
+                        volume: 60,
+                    },
+                    HitsoundSample {
+                        filename: String::from("soft-hitclap.wav"),
+                        fallback: None,
+                        volume: 60,
+                    },
+                ],
+            }]
+        );
+        // the inherited point 3ms later is close enough to apply
+        assert_eq!(
+            filenames(&hitsounds[1][0]),
+            vec!["drum-hitnormal2.wav", "soft-hitwhistle2.wav"]
+        );
+        assert_eq!(hitsounds[1][0].samples[0].volume, 80);
+        assert_eq!(
+            hitsounds[1][0].samples[0].fallback,
+            Some(String::from("drum-hitnormal.wav"))
+        );
+        assert_eq!(
+            hitsounds[2][0].samples,
+            vec![HitsoundSample {
+                filename: String::from("custom.wav"),
+                fallback: None,
+                volume: 40,
+            }]
+        );
+
+        assert_eq!(hitsounds[3].len(), 2);
+        assert_eq!(hitsounds[3][1].time, Time::from(3500));
+        assert_eq!(
+            filenames(&hitsounds[3][0]),
+            vec!["normal-hitnormal5.wav", "normal-hitwhistle5.wav"]
+        );
+        assert_eq!(
+            filenames(&hitsounds[3][1]),
+            vec!["drum-hitnormal5.wav", "normal-hitclap5.wav"]
+        );
+    }
+}
@@ -1,35 +1,90 @@
+#[cfg(feature = "rayon")]
+pub use crate::batch::{parse_songs_directory, parse_songs_directory_with_options, ParsedFile};
+pub use crate::{
+    error::{Diagnostic, Field, ParseError, ParseErrorKind, ReadError, Severity},
+    file_sections::FileSections,
+    header::{
+        parse_header, parse_header_with_counts, parse_header_with_counts_and_options,
+        parse_header_with_options, BeatmapHeader, ObjectCounts,
+    },
+    options::{LegacyEncoding, ParseMode, ParseOptions},
+    reader::{parse_file, parse_file_with_options, parse_reader, parse_reader_with_options},
+    stream::{BeatmapEvents, BeatmapItem},
+};
+
 use crate::{
+    colours::{parse_colours, Colours},
     difficulty::{parse_difficulty, Difficulty},
     editor::{parse_editor, Editor},
-    file_sections::FileSections,
-    format::{parse_format, Format},
-    general::{parse_general, General},
-    hit_objects::{parse_hit_objects, HitObject},
-    metadata::{parse_metadata, Metadata},
-    timing_points::{parse_timing_points, TimingPoint},
+    events::Event,
+    format::Format,
+    general::{parse_general, parse_general_ref, General, GeneralRef},
+    health::resolve_drain_rate,
+    hit_objects::HitObject,
+    hitsounds::{resolve_hitsounds, HitsoundEvent},
+    metadata::{parse_metadata, parse_metadata_ref, Metadata, MetadataRef},
+    stacking::{resolve_stacking, Stack},
+    storyboard::{parse_variables, Storyboard},
+    timing_points::{TimingModel, TimingPoint},
 };
 
-mod difficulty;
-mod editor;
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+#[cfg(feature = "rayon")]
+mod batch;
+pub mod colours;
+pub mod difficulty;
+pub mod editor;
+mod error;
+pub mod events;
 mod file_sections;
-mod format;
-mod general;
-mod hit_objects;
-mod metadata;
-mod timing_points;
+pub mod format;
+pub mod general;
+mod header;
+pub mod health;
+pub mod hit_objects;
+pub mod hitsounds;
+pub mod metadata;
+pub mod nested_objects;
+mod options;
+mod reader;
+pub mod slider_path;
+pub mod stacking;
+pub mod storyboard;
+mod stream;
+pub mod syntax;
+pub mod time;
+pub mod timing_points;
 
-#[derive(Default, Debug, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Beatmap {
     pub format: Format,
     pub general: General,
     pub editor: Editor,
     pub metadata: Metadata,
     pub difficulty: Difficulty,
+    pub events: Vec<Event>,
+    pub storyboard: Storyboard,
     pub timing_points: Vec<TimingPoint>,
+    pub colours: Colours,
     pub hit_objects: Vec<HitObject>,
+    /// Sections this crate doesn't know, in file order, written back after the known ones.
+    pub unknown_sections: Vec<UnknownSection>,
+}
+
+/// A section kept as its raw lines, without its `[Name]` header.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnknownSection {
+    pub name: String,
+    pub lines: Vec<String>,
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::too_many_arguments)]
 impl Beatmap {
     pub fn new(
         format: Format,
@@ -37,7 +92,10 @@ impl Beatmap {
         editor: Editor,
         metadata: Metadata,
         difficulty: Difficulty,
+        events: Vec<Event>,
+        storyboard: Storyboard,
         timing_points: Vec<TimingPoint>,
+        colours: Colours,
         hit_objects: Vec<HitObject>,
     ) -> Self {
         Self {
@@ -46,42 +104,295 @@ impl Beatmap {
             editor,
             metadata,
             difficulty,
+            events,
+            storyboard,
             timing_points,
+            colours,
             hit_objects,
+            unknown_sections: Vec::new(),
+        }
+    }
+
+    /// This difficulty's storyboard combined with the mapset's shared `.osb` storyboard, as the
+    /// game displays it.
+    pub fn merged_storyboard(&self, shared: &Storyboard) -> Storyboard {
+        Storyboard::merged(shared, &self.storyboard)
+    }
+
+    /// Looks up the BPM, slider velocity and sample settings in effect at any time.
+    pub fn timing(&self) -> TimingModel<'_> {
+        TimingModel::new(&self.timing_points)
+    }
+
+    /// The samples played by each hit object, in the same order as `hit_objects`.
+    pub fn hitsounds(&self) -> Vec<Vec<HitsoundEvent>> {
+        resolve_hitsounds(self)
+    }
+
+    /// The stack height and drawn position of each hit object, in the same order as
+    /// `hit_objects`.
+    pub fn stacking(&self) -> Vec<Stack> {
+        resolve_stacking(self)
+    }
+
+    /// The health drained per millisecond, found from the HP setting by simulating a full combo.
+    pub fn drain_rate(&self) -> f64 {
+        resolve_drain_rate(self)
+    }
+
+    /// Writes the beatmap as an `osu file format v14` document.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "{}", self)
+    }
+}
+
+/// A [`Beatmap`] whose `[General]` and `[Metadata]` strings and sample filenames are borrowed
+/// from the parsed text.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct BeatmapRef<'a> {
+    pub format: Format,
+    pub general: GeneralRef<'a>,
+    pub editor: Editor,
+    pub metadata: MetadataRef<'a>,
+    pub difficulty: Difficulty,
+    pub events: Vec<Event>,
+    pub storyboard: Storyboard,
+    pub timing_points: Vec<TimingPoint>,
+    pub colours: Colours,
+    pub hit_objects: Vec<HitObject<&'a str>>,
+    pub unknown_sections: Vec<UnknownSection>,
+}
+
+impl BeatmapRef<'_> {
+    /// Copies the borrowed strings. This takes `self` rather than being a `to_owned(&self)`, so the
+    /// sections that are already owned are moved instead of cloned, and so it doesn't shadow
+    /// [`ToOwned::to_owned`], which returns another `BeatmapRef`.
+    pub fn into_owned(self) -> Beatmap {
+        Beatmap {
+            format: self.format,
+            general: self.general.into_owned(),
+            editor: self.editor,
+            metadata: self.metadata.into_owned(),
+            difficulty: self.difficulty,
+            events: self.events,
+            storyboard: self.storyboard,
+            timing_points: self.timing_points,
+            colours: self.colours,
+            hit_objects: self
+                .hit_objects
+                .into_iter()
+                .map(HitObject::into_owned)
+                .collect(),
+            unknown_sections: self.unknown_sections,
         }
     }
 }
 
-pub fn parse(text: &str) -> Beatmap {
+impl fmt::Display for Beatmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.format)?;
+        writeln!(f, "[General]\n{}", self.general)?;
+        writeln!(f, "[Editor]\n{}", self.editor)?;
+        writeln!(f, "[Metadata]\n{}", self.metadata)?;
+        writeln!(f, "[Difficulty]\n{}", self.difficulty)?;
+
+        // events are written expanded, which reading the variables back doesn't change
+        if !self.storyboard.variables.is_empty() {
+            writeln!(f, "[Variables]")?;
+            for (name, value) in &self.storyboard.variables {
+                writeln!(f, "{}={}", name, value)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "[Events]")?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        writeln!(f, "{}", self.storyboard)?;
+
+        writeln!(f, "[TimingPoints]")?;
+        for timing_point in &self.timing_points {
+            writeln!(f, "{}", timing_point)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "[Colours]\n{}", self.colours)?;
+
+        writeln!(f, "[HitObjects]")?;
+        for hit_object in &self.hit_objects {
+            writeln!(f, "{}", hit_object)?;
+        }
+
+        for section in &self.unknown_sections {
+            writeln!(f, "\n[{}]", section.name)?;
+            for line in &section.lines {
+                writeln!(f, "{}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn parse(text: &str) -> Result<Beatmap, ParseError> {
+    parse_with_options(text, &ParseOptions::default()).map(|(beatmap, _)| beatmap)
+}
+
+/// Parses a beatmap, returning it together with the diagnostics collected along the way.
+///
+/// In [`ParseMode::Strict`] the first malformed line is returned as an error, while
+/// [`ParseMode::Lenient`] skips it and records it as a [`Severity::Error`] diagnostic instead.
+pub fn parse_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<(Beatmap, Vec<Diagnostic>), ParseError> {
+    parse_ref_with_options(text, options)
+        .map(|(beatmap, diagnostics)| (beatmap.into_owned(), diagnostics))
+}
+
+/// Parses a beatmap without copying the strings of `[General]` and `[Metadata]` or the sample
+/// filenames of hit objects, which make up most of the allocations when only scanning files.
+/// Event and storyboard filenames are still copied, as `[Variables]` can rewrite them.
+pub fn parse_ref(text: &str) -> Result<BeatmapRef<'_>, ParseError> {
+    parse_ref_with_options(text, &ParseOptions::default()).map(|(beatmap, _)| beatmap)
+}
+
+pub fn parse_ref_with_options<'a>(
+    text: &'a str,
+    options: &ParseOptions,
+) -> Result<(BeatmapRef<'a>, Vec<Diagnostic>), ParseError> {
     let mut beatmap = Beatmap::default();
-    let mut current_section = FileSections::None;
-
-    for line in text.lines() {
-        if !line.trim().is_empty() && !line.starts_with("//") {
-            if FileSections::get_section(line) != FileSections::None {
-                current_section = FileSections::get_section(line);
-            } else {
-                match current_section {
-                    FileSections::Format => parse_format(line, &mut beatmap),
-                    FileSections::General => parse_general(line, &mut beatmap),
-                    FileSections::Editor => parse_editor(line, &mut beatmap),
-                    FileSections::Difficulty => parse_difficulty(line, &mut beatmap),
-                    FileSections::Metadata => parse_metadata(line, &mut beatmap),
-                    FileSections::TimingPoints => parse_timing_points(line, &mut beatmap),
-                    FileSections::HitObjects => parse_hit_objects(line, &mut beatmap),
-                    _ => {}
-                }
+    let mut general = GeneralRef::default();
+    let mut metadata = MetadataRef::default();
+    let mut hit_objects = Vec::new();
+    let diagnostics = fold_items(text.lines(), options, |item, line| match item {
+        BeatmapItem::KeyValue {
+            section: FileSections::General,
+            ..
+        } => parse_general_ref(line, &mut general, &beatmap.format),
+        BeatmapItem::KeyValue {
+            section: FileSections::Metadata,
+            ..
+        } => parse_metadata_ref(line, &mut metadata),
+        BeatmapItem::HitObject(hit_object) => {
+            hit_objects.push(hit_object);
+            Ok(())
+        }
+        _ => apply_item(item, line, &mut beatmap),
+    })?;
+
+    let beatmap = BeatmapRef {
+        format: beatmap.format,
+        general,
+        editor: beatmap.editor,
+        metadata,
+        difficulty: beatmap.difficulty,
+        events: beatmap.events,
+        storyboard: beatmap.storyboard,
+        timing_points: beatmap.timing_points,
+        colours: beatmap.colours,
+        hit_objects,
+        unknown_sections: beatmap.unknown_sections,
+    };
+
+    Ok((beatmap, diagnostics))
+}
+
+/// Calls `apply` with every item of the beatmap made of `lines` and the line it was read from,
+/// collecting diagnostics according to `options`.
+pub(crate) fn fold_items<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    options: &ParseOptions,
+    mut apply: impl FnMut(BeatmapItem<'a>, &'a str) -> Result<(), ParseError>,
+) -> Result<Vec<Diagnostic>, ParseError> {
+    let mut diagnostics = Vec::new();
+    let mut items = BeatmapEvents::from_lines(lines);
+
+    while let Some(item) = items.next() {
+        match &item {
+            Ok(BeatmapItem::SectionStart {
+                section: FileSections::None,
+                ..
+            }) => diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                items.line_number(),
+                FileSections::None,
+                format!("unknown section `{}`", items.line().trim()),
+            )),
+            Ok(BeatmapItem::KeyValue {
+                section: FileSections::Colours,
+                key,
+                ..
+            }) if !colours::is_known_key(key) => diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                items.line_number(),
+                FileSections::Colours,
+                format!("unknown key `{}`", key),
+            )),
+            _ => {}
+        }
+
+        let result = item.and_then(|item| {
+            apply(item, items.line()).map_err(|e| e.with_line(items.line_number()))
+        });
+
+        match (result, options.mode) {
+            (Ok(()), _) => {}
+            (Err(e), ParseMode::Strict) => return Err(e),
+            (Err(e), ParseMode::Lenient) => diagnostics.push(Diagnostic::from(e)),
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+/// Adds an item to `beatmap`. Key-value lines are only checked here, so `line` must be the line
+/// the item was read from.
+pub(crate) fn apply_item(
+    item: BeatmapItem,
+    line: &str,
+    beatmap: &mut Beatmap,
+) -> Result<(), ParseError> {
+    match item {
+        BeatmapItem::Format(format) => beatmap.format = format,
+        BeatmapItem::SectionStart {
+            section: FileSections::None,
+            name,
+        } => beatmap.unknown_sections.push(UnknownSection {
+            name: String::from(name),
+            lines: Vec::new(),
+        }),
+        BeatmapItem::SectionStart { .. } => {}
+        BeatmapItem::KeyValue { section, .. } => match section {
+            FileSections::General => parse_general(line, beatmap)?,
+            FileSections::Editor => parse_editor(line, beatmap)?,
+            FileSections::Metadata => parse_metadata(line, beatmap)?,
+            FileSections::Difficulty => parse_difficulty(line, beatmap)?,
+            FileSections::Colours => parse_colours(line, beatmap)?,
+            FileSections::Variables => parse_variables(line, &mut beatmap.storyboard)?,
+            _ => {}
+        },
+        BeatmapItem::Event(event) => beatmap.events.push(event),
+        BeatmapItem::StoryboardObject(object) => beatmap.storyboard.objects.push(object),
+        BeatmapItem::TimingPoint(timing_point) => beatmap.timing_points.push(timing_point),
+        BeatmapItem::HitObject(hit_object) => beatmap.hit_objects.push(hit_object.into_owned()),
+        BeatmapItem::UnknownLine(text) => {
+            // lines before the first header have no section to belong to
+            if let Some(section) = beatmap.unknown_sections.last_mut() {
+                section.lines.push(String::from(text));
             }
         }
     }
 
-    beatmap
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::hit_objects::{HitCircle, HitSample};
+    use crate::hit_objects::{HitCircle, HitSample, Hitsound};
+    use crate::time::Time;
 
     #[test]
     fn test_parse() {
@@ -136,7 +447,7 @@ mod tests {
             256,192,11000,21,2
             256,192,11200,8,12,12000,3:0:0:80:
             100,100,12600,6,1,B|200:200|250:200|250:200|300:150,2,310.123,2|1|2,0:0|0:0|0:2,0:0:0:0:";
-        let beatmap = parse(test_str);
+        let beatmap = parse(test_str).unwrap();
 
         assert_eq!(beatmap.general.audio_filename, String::from("audio.mp3"));
         assert_eq!(beatmap.difficulty.cs, 4.2);
@@ -150,10 +461,181 @@ mod tests {
                 position_y: 192,
                 new_combo: true,
                 color_skip: 1,
-                time: 11000,
-                hitsound: 2,
+                time: Time::from(11000),
+                hitsound: Hitsound::WHISTLE,
                 hit_sample: HitSample::default(),
             })
         );
     }
+
+    #[test]
+    fn test_parse_error() {
+        let test_str = "osu file format v14
+
+            [General]
+            AudioFilename: audio.mp3
+            Countdown: 7";
+
+        assert_eq!(
+            parse(test_str),
+            Err(ParseError {
+                line: 5,
+                section: FileSections::General,
+                field: Some(Field::Key(String::from("Countdown"))),
+                kind: ParseErrorKind::InvalidValue,
+                text: String::from("            Countdown: 7"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let test_str = "osu file format v14
+
+            [General]
+            AudioFilename: audio.mp3
+            Countdown: 7
+
+            [Editor]
+            Bookmarks:
+
+            [Garbage]
+            Foo: Bar";
+        let (beatmap, diagnostics) =
+            parse_with_options(test_str, &ParseOptions::lenient()).unwrap();
+
+        assert_eq!(beatmap.general.audio_filename, String::from("audio.mp3"));
+        assert_eq!(beatmap.general.countdown, General::new().countdown);
+        assert_eq!(beatmap.editor.bookmarks, Vec::new());
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic::new(
+                    Severity::Error,
+                    5,
+                    FileSections::General,
+                    String::from("invalid value for `Countdown`: `Countdown: 7`"),
+                ),
+                Diagnostic::new(
+                    Severity::Warning,
+                    10,
+                    FileSections::None,
+                    String::from("unknown section `[Garbage]`"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_and_sections() {
+        let test_str = "osu file format v14
+
+            [General]
+            AudioFilename: audio.mp3
+            LazerOnlyKey: 1
+
+            [Metadata]
+            Title:Test
+            FutureKey:value
+
+            [Garbage]
+            Foo: Bar
+            1,2,3";
+        let beatmap = parse(test_str).unwrap();
+
+        assert_eq!(
+            beatmap.general.extras,
+            vec![(String::from("LazerOnlyKey"), String::from("1"))]
+        );
+        assert_eq!(
+            beatmap.metadata.extras,
+            vec![(String::from("FutureKey"), String::from("value"))]
+        );
+        assert_eq!(
+            beatmap.unknown_sections,
+            vec![UnknownSection {
+                name: String::from("Garbage"),
+                lines: vec![String::from("Foo: Bar"), String::from("1,2,3")],
+            }]
+        );
+
+        let written = beatmap.to_string();
+        assert!(written.contains("LazerOnlyKey: 1\n"));
+        assert!(written.contains("FutureKey:value\n"));
+        assert!(written.ends_with("[Garbage]\nFoo: Bar\n1,2,3\n"));
+        assert_eq!(parse(&written).unwrap(), beatmap);
+    }
+
+    #[test]
+    fn test_variables_round_trip() {
+        let test_str = [
+            "osu file format v14",
+            "",
+            "[Variables]",
+            "$pos=320,240",
+            "$centre=$pos",
+            "$loop=$loop",
+            "",
+            "[Events]",
+            "Sprite,Foreground,Centre,\"sb/$pos.png\",$centre",
+            " M,0,0,1000,$pos,$pos",
+        ]
+        .join("\n");
+        let beatmap = parse(&test_str).unwrap();
+        let written = beatmap.to_string();
+
+        assert!(written.contains("Sprite,Foreground,Centre,\"sb/$pos.png\",320,240\n"));
+        assert_eq!(parse(&written).unwrap(), beatmap);
+    }
+
+    #[test]
+    fn test_unknown_colour_key() {
+        let test_str = "osu file format v14
+
+            [Colours]
+            Combo1 : 255,192,0
+            SliderBody : 1,2,3";
+        let (beatmap, diagnostics) =
+            parse_with_options(test_str, &ParseOptions::default()).unwrap();
+
+        assert_eq!(
+            beatmap.colours.extras,
+            vec![(String::from("SliderBody"), String::from("1,2,3"))]
+        );
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::new(
+                Severity::Warning,
+                5,
+                FileSections::Colours,
+                String::from("unknown key `SliderBody`"),
+            )]
+        );
+        assert_eq!(parse(&beatmap.to_string()).unwrap(), beatmap);
+    }
+
+    #[test]
+    fn test_parse_ref() {
+        let test_str = "osu file format v14
+
+            [General]
+            AudioFilename: audio.mp3
+            PreviewTime: 1000
+
+            [Metadata]
+            Title:End Time
+            Tags:DeviousPanda cres
+
+            [HitObjects]
+            256,192,11000,21,2,0:0:0:0:clap.wav";
+        let beatmap = parse_ref(test_str).unwrap();
+
+        assert_eq!(beatmap.general.audio_filename, "audio.mp3");
+        assert_eq!(beatmap.metadata.title, "End Time");
+        assert_eq!(beatmap.metadata.tags, vec!["DeviousPanda", "cres"]);
+        assert!(
+            matches!(&beatmap.hit_objects[0], HitObject::HitCircle(t) if t.hit_sample.filename == "clap.wav")
+        );
+        assert_eq!(beatmap.into_owned(), parse(test_str).unwrap());
+    }
 }
@@ -1,19 +1,31 @@
-use crate::Beatmap;
+use crate::{
+    error::{split_key_value, ParseError},
+    file_sections::FileSections,
+    Beatmap,
+};
+use std::fmt;
 
-#[derive(Default, Debug, PartialEq)]
-pub struct Metadata {
-    pub title: String,
-    pub title_unicode: String,
-    pub artist: String,
-    pub artist_unicode: String,
-    pub creator: String,
-    pub version: String,
-    pub source: String,
-    pub tags: Vec<String>,
+/// The `[Metadata]` section. `S` is the type of its strings, `&str` for [`MetadataRef`].
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata<S = String> {
+    pub title: S,
+    pub title_unicode: S,
+    pub artist: S,
+    pub artist_unicode: S,
+    pub creator: S,
+    pub version: S,
+    pub source: S,
+    pub tags: Vec<S>,
     pub beatmap_id: u32,
     pub beatmap_set_id: u32,
+    /// Keys this crate doesn't know, in file order, written back after the known ones.
+    pub extras: Vec<(S, S)>,
 }
 
+/// [`Metadata`] with its strings borrowed from the parsed text.
+pub type MetadataRef<'a> = Metadata<&'a str>;
+
 #[allow(clippy::too_many_arguments)]
 impl Metadata {
     pub fn new(
@@ -39,31 +51,99 @@ impl Metadata {
             tags,
             beatmap_id,
             beatmap_set_id,
+            extras: Vec::new(),
         }
     }
 }
 
-pub fn parse_metadata(line: &str, beatmap: &mut Beatmap) {
-    let (k, v) = line.split_once(':').unwrap();
-    match k.trim() {
-        "Title" => beatmap.metadata.title = String::from(v.trim()),
-        "TitleUnicode" => beatmap.metadata.title_unicode = String::from(v.trim()),
-        "Artist" => beatmap.metadata.artist = String::from(v.trim()),
-        "ArtistUnicode" => beatmap.metadata.artist_unicode = String::from(v.trim()),
-        "Creator" => beatmap.metadata.creator = String::from(v.trim()),
-        "Version" => beatmap.metadata.version = String::from(v.trim()),
-        "Source" => beatmap.metadata.source = String::from(v.trim()),
-        "Tags" => beatmap.metadata.tags = v.trim().split(' ').map(String::from).collect(),
-        "BeatmapID" => beatmap.metadata.beatmap_id = v.trim().parse::<u32>().unwrap(),
-        "BeatmapSetID" => beatmap.metadata.beatmap_set_id = v.trim().parse::<u32>().unwrap(),
-        _ => {}
+impl<S: AsRef<str>> fmt::Display for Metadata<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tags: Vec<&str> = self.tags.iter().map(AsRef::as_ref).collect();
+
+        writeln!(f, "Title:{}", self.title.as_ref())?;
+        writeln!(f, "TitleUnicode:{}", self.title_unicode.as_ref())?;
+        writeln!(f, "Artist:{}", self.artist.as_ref())?;
+        writeln!(f, "ArtistUnicode:{}", self.artist_unicode.as_ref())?;
+        writeln!(f, "Creator:{}", self.creator.as_ref())?;
+        writeln!(f, "Version:{}", self.version.as_ref())?;
+        writeln!(f, "Source:{}", self.source.as_ref())?;
+        writeln!(f, "Tags:{}", tags.join(" "))?;
+        writeln!(f, "BeatmapID:{}", self.beatmap_id)?;
+        writeln!(f, "BeatmapSetID:{}", self.beatmap_set_id)?;
+        for (key, value) in &self.extras {
+            writeln!(f, "{}:{}", key.as_ref(), value.as_ref())?;
+        }
+
+        Ok(())
     }
 }
 
+/// `Tags` and `Source` are read the same way for every format version, as the game itself does:
+/// tags are split on whitespace and the source is kept as written.
+pub fn parse_metadata(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    read_metadata(line, &mut beatmap.metadata)
+}
+
+impl MetadataRef<'_> {
+    pub fn into_owned(self) -> Metadata {
+        Metadata {
+            title: String::from(self.title),
+            title_unicode: String::from(self.title_unicode),
+            artist: String::from(self.artist),
+            artist_unicode: String::from(self.artist_unicode),
+            creator: String::from(self.creator),
+            version: String::from(self.version),
+            source: String::from(self.source),
+            tags: self.tags.into_iter().map(String::from).collect(),
+            beatmap_id: self.beatmap_id,
+            beatmap_set_id: self.beatmap_set_id,
+            extras: self
+                .extras
+                .into_iter()
+                .map(|(k, v)| (String::from(k), String::from(v)))
+                .collect(),
+        }
+    }
+}
+
+/// Reads a line of `[Metadata]` without copying any string out of it.
+pub fn parse_metadata_ref<'a>(
+    line: &'a str,
+    metadata: &mut MetadataRef<'a>,
+) -> Result<(), ParseError> {
+    read_metadata(line, metadata)
+}
+
+/// Reads a line of `[Metadata]` into either kind of [`Metadata`], copying strings out of the line
+/// only when `S` owns them.
+fn read_metadata<'a, S: From<&'a str>>(
+    line: &'a str,
+    metadata: &mut Metadata<S>,
+) -> Result<(), ParseError> {
+    let (k, v) = split_key_value(line, FileSections::Metadata)?;
+    let invalid = || ParseError::invalid_key(FileSections::Metadata, line, k);
+
+    match k {
+        "Title" => metadata.title = S::from(v),
+        "TitleUnicode" => metadata.title_unicode = S::from(v),
+        "Artist" => metadata.artist = S::from(v),
+        "ArtistUnicode" => metadata.artist_unicode = S::from(v),
+        "Creator" => metadata.creator = S::from(v),
+        "Version" => metadata.version = S::from(v),
+        "Source" => metadata.source = S::from(v),
+        "Tags" => metadata.tags = v.split_whitespace().map(S::from).collect(),
+        "BeatmapID" => metadata.beatmap_id = v.parse::<u32>().map_err(|_| invalid())?,
+        "BeatmapSetID" => metadata.beatmap_set_id = v.parse::<u32>().map_err(|_| invalid())?,
+        _ => metadata.extras.push((S::from(k), S::from(v))),
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Difficulty, Editor, Format, General};
+    use crate::{Colours, Difficulty, Editor, Format, General, Storyboard};
 
     #[test]
     fn test_parse_metadata() {
@@ -79,7 +159,7 @@ mod tests {
             BeatmapSetID:1351450";
         let mut beatmap = Beatmap::default();
         for line in test_str.lines() {
-            parse_metadata(line, &mut beatmap);
+            parse_metadata(line, &mut beatmap).unwrap();
         }
 
         assert_eq!(
@@ -99,10 +179,15 @@ mod tests {
                     tags: vec![String::from("DeviousPanda")],
                     beatmap_id: 2797865,
                     beatmap_set_id: 1351450,
+                    extras: Vec::new(),
                 },
                 difficulty: Difficulty::default(),
+                events: Vec::new(),
+                storyboard: Storyboard::default(),
                 timing_points: Vec::new(),
+                colours: Colours::default(),
                 hit_objects: Vec::new(),
+                unknown_sections: Vec::new(),
             }
         );
     }
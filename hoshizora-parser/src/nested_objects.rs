@@ -0,0 +1,237 @@
+use crate::{
+    difficulty::Difficulty, format::Format, hit_objects::Slider, time::Time,
+    timing_points::TimingModel,
+};
+
+/// How long before the end of a slider the game judges its tail, in milliseconds.
+const LEGACY_TAIL_OFFSET: Time = Time::from_ms(36.0);
+/// Ticks closer than this to the end of a span are skipped, in milliseconds of travel.
+const TICK_END_LENIENCY: f64 = 10.0;
+/// The longest distance ticks are placed along, in osu!pixels.
+const MAX_TICK_LENGTH: f64 = 100000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NestedObjectKind {
+    Head,
+    Tick,
+    /// The end of every slide but the last.
+    Repeat,
+    /// The end of the last slide, judged a little early like the game does.
+    Tail,
+}
+
+/// A point of a slider that is judged on its own.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NestedObject {
+    pub kind: NestedObjectKind,
+    pub time: Time,
+    pub position: (f64, f64),
+    /// The slide the object belongs to, from 0.
+    pub span_index: u32,
+    /// How far along the path the object is, from 0 at the head to 1 at the end.
+    pub path_progress: f64,
+}
+
+impl<S> Slider<S> {
+    /// The head, ticks, repeats and tail of the slider, sorted by time.
+    ///
+    /// Ticks are placed every `100 * slider multiplier * velocity / tick rate` osu!pixels, except
+    /// within 10ms of travel of the end of a span. Before v8, the velocity of inherited points
+    /// didn't change that distance. The tail is judged 36ms before the end of the slider, but
+    /// no earlier than its middle.
+    pub fn nested_objects(
+        &self,
+        timing: &TimingModel,
+        difficulty: &Difficulty,
+        format: &Format,
+    ) -> Vec<NestedObject> {
+        let path = self.path();
+        let slider_velocity = timing.slider_velocity_at(self.time);
+        let scoring_distance = 100.0 * difficulty.effective_slider_multiplier() * slider_velocity;
+        let velocity = scoring_distance / timing.beat_length_at(self.time);
+        let span_duration = self.span_duration(timing, difficulty);
+        let spans = self.slides.max(1);
+
+        let tick_distance_multiplier = match format.version {
+            ..=7 => 1.0 / slider_velocity,
+            _ => 1.0,
+        };
+        let length = self.length.min(MAX_TICK_LENGTH);
+        let tick_distance = (scoring_distance / difficulty.slider_tickrate
+            * tick_distance_multiplier)
+            .clamp(0.0, length.max(0.0));
+        let min_distance_from_end = velocity * TICK_END_LENIENCY;
+
+        let nested = |kind, time, span_index, path_progress| NestedObject {
+            kind,
+            time,
+            position: path.position_at(path_progress),
+            span_index,
+            path_progress,
+        };
+
+        let mut objects = vec![nested(NestedObjectKind::Head, self.time, 0, 0.0)];
+        for span in 0..spans {
+            let span_start = self.time + span_duration * f64::from(span);
+            let reversed = span % 2 == 1;
+
+            if tick_distance > 0.0 {
+                let mut ticks = Vec::new();
+                let mut distance = tick_distance;
+                while distance <= length && distance < length - min_distance_from_end {
+                    let path_progress = distance / length;
+                    let time_progress = match reversed {
+                        true => 1.0 - path_progress,
+                        false => path_progress,
+                    };
+                    ticks.push(nested(
+                        NestedObjectKind::Tick,
+                        span_start + span_duration * time_progress,
+                        span,
+                        path_progress,
+                    ));
+                    distance += tick_distance;
+                }
+                if reversed {
+                    ticks.reverse();
+                }
+                objects.extend(ticks);
+            }
+
+            if span < spans - 1 {
+                objects.push(nested(
+                    NestedObjectKind::Repeat,
+                    span_start + span_duration,
+                    span,
+                    f64::from((span + 1) % 2),
+                ));
+            }
+        }
+
+        let total_duration = span_duration * f64::from(spans);
+        let final_span_start = self.time + span_duration * f64::from(spans - 1);
+        let tail_time = (final_span_start + span_duration - LEGACY_TAIL_OFFSET)
+            .max(self.time + total_duration * 0.5);
+        let progress = match span_duration.as_ms() > 0.0 {
+            true => (tail_time - final_span_start).as_ms() / span_duration.as_ms(),
+            false => 1.0,
+        };
+        // the last span runs back towards the head after an even number of slides
+        let tail_progress = match spans % 2 {
+            0 => 1.0 - progress,
+            _ => progress,
+        };
+        objects.push(nested(
+            NestedObjectKind::Tail,
+            tail_time,
+            spans - 1,
+            tail_progress,
+        ));
+
+        objects.sort_by(|a, b| a.time.as_ms().total_cmp(&b.time.as_ms()));
+        objects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hit_objects::HitObject, parse};
+
+    fn nested_objects(test_str: &str) -> Vec<NestedObject> {
+        let beatmap = parse(test_str).unwrap();
+        match &beatmap.hit_objects[0] {
+            HitObject::Slider(t) => {
+                t.nested_objects(&beatmap.timing(), &beatmap.difficulty, &beatmap.format)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_nested_objects() {
+        let test_str = "osu file format v14
+
+            [Difficulty]
+            SliderMultiplier:1
+            SliderTickRate:2
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+
+            [HitObjects]
+            0,0,0,2,0,L|200:0,2,200";
+        let objects = nested_objects(test_str);
+        let kinds: Vec<NestedObjectKind> = objects.iter().map(|t| t.kind).collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                NestedObjectKind::Head,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Repeat,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Tick,
+                NestedObjectKind::Tail,
+            ]
+        );
+        assert_eq!(objects[1].time, Time::from(250));
+        assert_eq!(objects[1].position, (50.0, 0.0));
+        assert_eq!(objects[4].time, Time::from(1000));
+        assert_eq!(objects[4].position, (200.0, 0.0));
+        // the second span travels back towards the head
+        assert_eq!(objects[5].time, Time::from(1250));
+        assert_eq!(objects[5].position, (150.0, 0.0));
+        assert_eq!(objects[5].span_index, 1);
+        assert_eq!(objects[8].time, Time::from(1964));
+        assert!((objects[8].position.0 - 7.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_nested_objects_tick_near_end() {
+        // the tick at 100px would be 5ms before the end
+        let test_str = "osu file format v14
+
+            [Difficulty]
+            SliderMultiplier:1
+            SliderTickRate:1
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+
+            [HitObjects]
+            0,0,0,2,0,L|101:0,1,101";
+        let objects = nested_objects(test_str);
+
+        assert!(objects.iter().all(|t| t.kind != NestedObjectKind::Tick));
+    }
+
+    #[test]
+    fn test_nested_objects_legacy_tick_distance() {
+        let test_str = "osu file format v7
+
+            [Difficulty]
+            SliderMultiplier:1
+            SliderTickRate:1
+
+            [TimingPoints]
+            0,500,4,2,1,60,1,0
+            0,-50,4,2,1,60,0,0
+
+            [HitObjects]
+            0,0,0,2,0,L|400:0,1,400";
+        let objects = nested_objects(test_str);
+        let ticks = objects
+            .iter()
+            .filter(|t| t.kind == NestedObjectKind::Tick)
+            .count();
+
+        // ticks stay 100px apart instead of 200px
+        assert_eq!(ticks, 3);
+    }
+}
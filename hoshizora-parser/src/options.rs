@@ -0,0 +1,47 @@
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ParseMode {
+    /// Stop at the first line that fails to parse.
+    #[default]
+    Strict,
+    /// Skip lines that fail to parse, leaving the affected fields at their defaults, and report
+    /// them as diagnostics.
+    Lenient,
+}
+
+/// Single-byte and multi-byte encodings used by old editors, before files were saved as UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LegacyEncoding {
+    ShiftJis,
+    Windows1252,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    /// Encoding to decode files with when they're neither valid UTF-8 nor UTF-16. Only used when
+    /// parsing from bytes, with [`parse_reader`](crate::parse_reader) or
+    /// [`parse_file`](crate::parse_file).
+    pub legacy_encoding: Option<LegacyEncoding>,
+}
+
+impl ParseOptions {
+    pub fn new(mode: ParseMode) -> Self {
+        Self {
+            mode,
+            legacy_encoding: None,
+        }
+    }
+
+    pub fn with_legacy_encoding(mut self, legacy_encoding: LegacyEncoding) -> Self {
+        self.legacy_encoding = Some(legacy_encoding);
+        self
+    }
+
+    pub fn strict() -> Self {
+        Self::new(ParseMode::Strict)
+    }
+
+    pub fn lenient() -> Self {
+        Self::new(ParseMode::Lenient)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    error::{Diagnostic, ReadError},
+    options::{LegacyEncoding, ParseOptions},
+    parse_with_options, Beatmap,
+};
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use std::{borrow::Cow, fs, io::Read, path::Path};
+
+/// Decodes the bytes of a beatmap file to text.
+///
+/// A UTF-8 byte order mark is stripped, UTF-16 is recognised either by its byte order mark or by
+/// the zero bytes of the ASCII `osu file format` header, and anything else must be UTF-8 unless
+/// a legacy encoding is given.
+pub fn decode(
+    bytes: &[u8],
+    legacy_encoding: Option<LegacyEncoding>,
+) -> Result<Cow<'_, str>, ReadError> {
+    let decode_with = |encoding: &'static Encoding, bytes| {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .ok_or(ReadError::InvalidEncoding)
+    };
+
+    match bytes {
+        [0xef, 0xbb, 0xbf, rest @ ..] => std::str::from_utf8(rest)
+            .map(Cow::Borrowed)
+            .map_err(|_| ReadError::InvalidEncoding),
+        [0xff, 0xfe, rest @ ..] => decode_with(UTF_16LE, rest),
+        [0xfe, 0xff, rest @ ..] => decode_with(UTF_16BE, rest),
+        [first, 0, ..] if *first != 0 => decode_with(UTF_16LE, bytes),
+        [0, second, ..] if *second != 0 => decode_with(UTF_16BE, bytes),
+        _ => match (std::str::from_utf8(bytes), legacy_encoding) {
+            (Ok(text), _) => Ok(Cow::Borrowed(text)),
+            (Err(_), Some(LegacyEncoding::ShiftJis)) => decode_with(SHIFT_JIS, bytes),
+            (Err(_), Some(LegacyEncoding::Windows1252)) => decode_with(WINDOWS_1252, bytes),
+            (Err(_), None) => Err(ReadError::InvalidEncoding),
+        },
+    }
+}
+
+pub fn parse_reader(reader: impl Read) -> Result<Beatmap, ReadError> {
+    parse_reader_with_options(reader, &ParseOptions::default()).map(|(beatmap, _)| beatmap)
+}
+
+pub fn parse_reader_with_options(
+    mut reader: impl Read,
+    options: &ParseOptions,
+) -> Result<(Beatmap, Vec<Diagnostic>), ReadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    let text = decode(&bytes, options.legacy_encoding)?;
+
+    Ok(parse_with_options(&text, options)?)
+}
+
+pub fn parse_file(path: impl AsRef<Path>) -> Result<Beatmap, ReadError> {
+    parse_file_with_options(path, &ParseOptions::default()).map(|(beatmap, _)| beatmap)
+}
+
+pub fn parse_file_with_options(
+    path: impl AsRef<Path>,
+    options: &ParseOptions,
+) -> Result<(Beatmap, Vec<Diagnostic>), ReadError> {
+    parse_reader_with_options(fs::File::open(path)?, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_STR: &str = "osu file format v14\r\n\r\n[Metadata]\r\nTitle:東京\r\n";
+
+    #[test]
+    fn test_parse_reader_bom() {
+        let bytes = [&[0xef, 0xbb, 0xbf], TEST_STR.as_bytes()].concat();
+        let beatmap = parse_reader(&bytes[..]).unwrap();
+
+        assert_eq!(beatmap.metadata.title, String::from("東京"));
+    }
+
+    #[test]
+    fn test_parse_reader_utf16() {
+        let little_endian: Vec<u8> = TEST_STR.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let big_endian: Vec<u8> = [0xfe, 0xff]
+            .into_iter()
+            .chain(TEST_STR.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+
+        assert_eq!(decode(&little_endian, None).unwrap(), TEST_STR);
+        assert_eq!(decode(&big_endian, None).unwrap(), TEST_STR);
+    }
+
+    #[test]
+    fn test_parse_reader_legacy_encoding() {
+        let (bytes, _, _) = SHIFT_JIS.encode(TEST_STR);
+
+        assert!(matches!(
+            parse_reader(&bytes[..]),
+            Err(ReadError::InvalidEncoding)
+        ));
+
+        let options = ParseOptions::default().with_legacy_encoding(LegacyEncoding::ShiftJis);
+        let (beatmap, _) = parse_reader_with_options(&bytes[..], &options).unwrap();
+        assert_eq!(beatmap.metadata.title, String::from("東京"));
+    }
+}
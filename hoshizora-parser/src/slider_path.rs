@@ -0,0 +1,487 @@
+use crate::hit_objects::{CurveType, Slider};
+use std::{
+    f64::consts::PI,
+    ops::{Add, Mul, Sub},
+};
+
+/// How far, in osu!pixels, the line segments approximating a Bezier curve may stray from it.
+const BEZIER_TOLERANCE: f64 = 0.25;
+/// How far the line segments approximating a circular arc may stray from it.
+const CIRCULAR_ARC_TOLERANCE: f64 = 0.1;
+/// Arcs that would need more points than this are approximated as Bezier curves instead.
+const MAX_CIRCULAR_ARC_POINTS: f64 = 1000.0;
+/// The number of line segments between two control points of a Catmull-Rom curve.
+const CATMULL_DETAIL: usize = 50;
+
+/// The path followed by a slider's ball, approximated by line segments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliderPath {
+    points: Vec<(f64, f64)>,
+    /// The distance along the path to each point.
+    cumulative_length: Vec<f64>,
+}
+
+impl SliderPath {
+    /// Builds the path through `control_points`, where a repeated point starts a new segment
+    /// outside Catmull curves. The path is cut or extended to `length` if it's positive.
+    pub fn new(curve_type: &CurveType, control_points: &[(f64, f64)], length: f64) -> Self {
+        let control_points: Vec<Vec2> = control_points.iter().copied().map(Vec2::from).collect();
+        // a perfect curve needs exactly three points to define its circle
+        let curve_type = match curve_type {
+            CurveType::Perfect if control_points.len() != 3 => &CurveType::Bezier,
+            t => t,
+        };
+
+        let segments = match curve_type {
+            CurveType::Catmull if control_points.len() > 1 => vec![&control_points[..]],
+            _ => segments(&control_points),
+        };
+
+        let mut points: Vec<Vec2> = Vec::new();
+        for segment in segments {
+            let approximated = match curve_type {
+                CurveType::Linear => segment.to_vec(),
+                CurveType::Bezier => bezier(segment),
+                CurveType::Catmull => catmull(segment),
+                CurveType::Perfect => circular_arc(segment).unwrap_or_else(|| bezier(segment)),
+            };
+            for point in approximated {
+                if points.last() != Some(&point) {
+                    points.push(point);
+                }
+            }
+        }
+        if points.is_empty() {
+            points.extend(control_points.first());
+        }
+
+        let mut path = Self {
+            points: points.into_iter().map(<(f64, f64)>::from).collect(),
+            cumulative_length: Vec::new(),
+        };
+        path.fit_to_length(length);
+        path
+    }
+
+    /// The points of the line segments approximating the path.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    pub fn length(&self) -> f64 {
+        self.cumulative_length.last().copied().unwrap_or_default()
+    }
+
+    /// The position at `progress` of the way along the path, from 0 at its start to 1 at its
+    /// end.
+    pub fn position_at(&self, progress: f64) -> (f64, f64) {
+        let distance = progress.clamp(0.0, 1.0) * self.length();
+        let index = self.cumulative_length.partition_point(|t| *t < distance);
+
+        if index == 0 {
+            return self.points.first().copied().unwrap_or_default();
+        }
+        if index >= self.points.len() {
+            return self.points.last().copied().unwrap_or_default();
+        }
+
+        let (start, end) = (
+            Vec2::from(self.points[index - 1]),
+            Vec2::from(self.points[index]),
+        );
+        let (start_length, end_length) = (
+            self.cumulative_length[index - 1],
+            self.cumulative_length[index],
+        );
+        if end_length - start_length <= f64::EPSILON {
+            return start.into();
+        }
+        let weight = (distance - start_length) / (end_length - start_length);
+
+        (start + (end - start) * weight).into()
+    }
+
+    pub fn end_position(&self) -> (f64, f64) {
+        self.position_at(1.0)
+    }
+
+    /// Moves the last point so the path is `length` long, dropping the points past it. A path
+    /// whose last two points are equal is never extended, as the game doesn't know which way.
+    fn fit_to_length(&mut self, length: f64) {
+        self.cumulative_length = Vec::with_capacity(self.points.len());
+        let mut total = 0.0;
+        for (index, point) in self.points.iter().enumerate() {
+            if index > 0 {
+                total += (Vec2::from(*point) - Vec2::from(self.points[index - 1])).length();
+            }
+            self.cumulative_length.push(total);
+        }
+
+        if length <= 0.0 || total == length || self.points.len() < 2 {
+            return;
+        }
+
+        self.cumulative_length.pop();
+        if length > total {
+            let end = self.points.len() - 1;
+            if self.points[end - 1] == self.points[end] {
+                self.cumulative_length.push(total);
+                return;
+            }
+        } else {
+            while self.cumulative_length.last().is_some_and(|t| *t >= length) {
+                self.cumulative_length.pop();
+                self.points.pop();
+            }
+        }
+
+        let end = self.points.len() - 1;
+        let start = Vec2::from(self.points[end - 1]);
+        let direction = Vec2::from(self.points[end]) - start;
+        let remaining = length - self.cumulative_length[end - 1];
+        self.points[end] = (start + direction * (remaining / direction.length())).into();
+        self.cumulative_length.push(length);
+    }
+}
+
+impl<S> Slider<S> {
+    /// The path of the slider from its position through its curve points, fitted to its length.
+    pub fn path(&self) -> SliderPath {
+        let control_points: Vec<(f64, f64)> = [(self.position_x, self.position_y)]
+            .iter()
+            .chain(&self.curve_points)
+            .map(|(x, y)| (f64::from(*x), f64::from(*y)))
+            .collect();
+
+        SliderPath::new(&self.curve_type, &control_points, self.length)
+    }
+
+    /// Where the slider ends: the end of its path after an odd number of slides, and its head
+    /// after an even number.
+    pub fn end_position(&self) -> (f64, f64) {
+        match self.slides % 2 {
+            0 => (f64::from(self.position_x), f64::from(self.position_y)),
+            _ => self.path().end_position(),
+        }
+    }
+}
+
+/// Splits the control points at each repeated point.
+fn segments(control_points: &[Vec2]) -> Vec<&[Vec2]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for index in 1..control_points.len() {
+        if control_points[index] == control_points[index - 1] {
+            segments.push(&control_points[start..index]);
+            start = index;
+        }
+    }
+    segments.push(&control_points[start..]);
+
+    segments.retain(|t| t.len() > 1);
+    segments
+}
+
+/// Subdivides the curve until each piece is flat enough to be drawn as the midpoints of its
+/// control points.
+fn bezier(control_points: &[Vec2]) -> Vec<Vec2> {
+    let mut output = Vec::new();
+    approximate_bezier(control_points, &mut output);
+    output.extend(control_points.last());
+    output
+}
+
+fn approximate_bezier(control_points: &[Vec2], output: &mut Vec<Vec2>) {
+    let (left, right) = subdivide(control_points);
+    if !is_flat_enough(control_points) {
+        approximate_bezier(&left, output);
+        approximate_bezier(&right, output);
+        return;
+    }
+
+    let count = control_points.len();
+    let combined: Vec<Vec2> = left.iter().chain(&right[1..]).copied().collect();
+    output.push(control_points[0]);
+    for index in (1..count - 1).map(|t| t * 2) {
+        output.push((combined[index - 1] + combined[index] * 2.0 + combined[index + 1]) * 0.25);
+    }
+}
+
+fn is_flat_enough(control_points: &[Vec2]) -> bool {
+    control_points.windows(3).all(|t| {
+        (t[0] - t[1] * 2.0 + t[2]).length_squared() <= BEZIER_TOLERANCE * BEZIER_TOLERANCE * 4.0
+    })
+}
+
+/// Splits a Bezier curve in two halves with de Casteljau's algorithm.
+fn subdivide(control_points: &[Vec2]) -> (Vec<Vec2>, Vec<Vec2>) {
+    let count = control_points.len();
+    let mut midpoints = control_points.to_vec();
+    let mut left = vec![Vec2::default(); count];
+    let mut right = vec![Vec2::default(); count];
+
+    for i in 0..count {
+        left[i] = midpoints[0];
+        right[count - i - 1] = midpoints[count - i - 1];
+        for j in 0..count - i - 1 {
+            midpoints[j] = (midpoints[j] + midpoints[j + 1]) * 0.5;
+        }
+    }
+
+    (left, right)
+}
+
+fn catmull(control_points: &[Vec2]) -> Vec<Vec2> {
+    let count = control_points.len();
+    let mut output = Vec::with_capacity((count - 1) * CATMULL_DETAIL + 1);
+
+    for i in 0..count - 1 {
+        let v1 = if i > 0 {
+            control_points[i - 1]
+        } else {
+            control_points[i]
+        };
+        let v2 = control_points[i];
+        let v3 = control_points[i + 1];
+        let v4 = match control_points.get(i + 2) {
+            Some(t) => *t,
+            None => v3 * 2.0 - v2,
+        };
+
+        for c in 0..CATMULL_DETAIL {
+            let t = c as f64 / CATMULL_DETAIL as f64;
+            let (t2, t3) = (t * t, t * t * t);
+            output.push(
+                (v2 * 2.0
+                    + (v3 - v1) * t
+                    + (v1 * 2.0 - v2 * 5.0 + v3 * 4.0 - v4) * t2
+                    + (v2 * 3.0 - v1 - v3 * 3.0 + v4) * t3)
+                    * 0.5,
+            );
+        }
+    }
+    output.push(control_points[count - 1]);
+
+    output
+}
+
+/// The arc from the first to the last point through the middle one, or `None` when they're too
+/// close to a line to define a circle or the circle is too large to approximate.
+fn circular_arc(control_points: &[Vec2]) -> Option<Vec<Vec2>> {
+    let [a, b, c] = control_points[..] else {
+        return None;
+    };
+    if ((b.y - a.y) * (c.x - a.x) - (b.x - a.x) * (c.y - a.y)).abs() < 1e-3 {
+        return None;
+    }
+
+    let d = 2.0 * (a.x * (b.y - c.y) + b.x * (c.y - a.y) + c.x * (a.y - b.y));
+    let (a_sq, b_sq, c_sq) = (a.length_squared(), b.length_squared(), c.length_squared());
+    let centre = Vec2 {
+        x: (a_sq * (b.y - c.y) + b_sq * (c.y - a.y) + c_sq * (a.y - b.y)) / d,
+        y: (a_sq * (c.x - b.x) + b_sq * (a.x - c.x) + c_sq * (b.x - a.x)) / d,
+    };
+
+    let radius = (a - centre).length();
+    let theta_start = (a.y - centre.y).atan2(a.x - centre.x);
+    let mut theta_end = (c.y - centre.y).atan2(c.x - centre.x);
+    while theta_end < theta_start {
+        theta_end += 2.0 * PI;
+    }
+
+    let mut direction = 1.0;
+    let mut theta_range = theta_end - theta_start;
+    // the middle point decides which way around the circle the arc goes
+    let ortho_a_to_c = Vec2 {
+        x: c.y - a.y,
+        y: -(c.x - a.x),
+    };
+    if ortho_a_to_c.dot(b - a) < 0.0 {
+        direction = -1.0;
+        theta_range = 2.0 * PI - theta_range;
+    }
+
+    let point_count = if 2.0 * radius <= CIRCULAR_ARC_TOLERANCE {
+        2
+    } else {
+        let step = 2.0 * (1.0 - CIRCULAR_ARC_TOLERANCE / radius).acos();
+        let count = (theta_range / step).ceil();
+        if !(step.is_finite() && step > 0.0) || count > MAX_CIRCULAR_ARC_POINTS {
+            return None;
+        }
+        (count as usize).max(2)
+    };
+
+    Some(
+        (0..point_count)
+            .map(|i| {
+                let theta =
+                    theta_start + direction * i as f64 / (point_count - 1) as f64 * theta_range;
+                centre
+                    + Vec2 {
+                        x: theta.cos(),
+                        y: theta.sin(),
+                    } * radius
+            })
+            .collect(),
+    )
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Vec2 {
+    x: f64,
+    y: f64,
+}
+
+impl Vec2 {
+    fn dot(self, rhs: Self) -> f64 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    fn length_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    fn length(self) -> f64 {
+        self.length_squared().sqrt()
+    }
+}
+
+impl From<(f64, f64)> for Vec2 {
+    fn from((x, y): (f64, f64)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for (f64, f64) {
+    fn from(vec: Vec2) -> Self {
+        (vec.x, vec.y)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self {
+            x: self.x * rhs,
+            y: self.y * rhs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{format::Format, hit_objects::parse_hit_object, hit_objects::HitObject};
+
+    fn assert_near((x, y): (f64, f64), (expected_x, expected_y): (f64, f64)) {
+        assert!(
+            (x - expected_x).abs() < 0.5 && (y - expected_y).abs() < 0.5,
+            "({}, {}) is not near ({}, {})",
+            x,
+            y,
+            expected_x,
+            expected_y
+        );
+    }
+
+    fn slider(line: &str) -> Slider {
+        match parse_hit_object(line, &Format::default()).unwrap() {
+            HitObject::Slider(t) => t,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_linear_path() {
+        let path = slider("0,0,0,2,0,L|100:0|100:100,1,150").path();
+
+        assert_eq!(path.points(), &[(0.0, 0.0), (100.0, 0.0), (100.0, 50.0)]);
+        assert_eq!(path.length(), 150.0);
+        assert_eq!(path.position_at(0.5), (75.0, 0.0));
+        assert_eq!(path.end_position(), (100.0, 50.0));
+
+        // extended in the direction of the last segment
+        let path = slider("0,0,0,2,0,L|100:0,1,120").path();
+        assert_eq!(path.end_position(), (120.0, 0.0));
+    }
+
+    #[test]
+    fn test_bezier_path() {
+        // the repeated point makes a sharp corner
+        let path = slider("0,0,0,2,0,B|100:0|100:0|100:100,1,200").path();
+
+        assert_near(path.position_at(0.5), (100.0, 0.0));
+        assert_near(path.end_position(), (100.0, 100.0));
+
+        let path = slider("0,0,0,2,0,B|100:100|200:0,1,100").path();
+        assert!(path.points().len() > 3);
+        assert_eq!(path.points()[0], (0.0, 0.0));
+        assert!(path.points().iter().all(|(_, y)| *y <= 50.0));
+    }
+
+    #[test]
+    fn test_perfect_path() {
+        let length = 100.0 * PI;
+        let path = SliderPath::new(
+            &CurveType::Perfect,
+            &[(0.0, 0.0), (100.0, 100.0), (200.0, 0.0)],
+            length,
+        );
+
+        assert_near(path.position_at(0.5), (100.0, 100.0));
+        assert_near(path.end_position(), (200.0, 0.0));
+
+        // collinear points fall back to a Bezier curve
+        let path = slider("0,0,0,2,0,P|100:0|200:0,1,200").path();
+        assert_near(path.end_position(), (200.0, 0.0));
+
+        // so does a circle too large to approximate
+        let path = slider("0,0,0,2,0,P|1000000:1|2000001:2,1,100").path();
+        assert_eq!(path.length(), 100.0);
+    }
+
+    #[test]
+    fn test_catmull_path() {
+        let path = slider("0,0,0,2,0,C|100:50|200:0,1,0").path();
+
+        assert!(path.points().contains(&(100.0, 50.0)));
+        assert_eq!(path.end_position(), (200.0, 0.0));
+
+        // a repeated point doesn't make a corner: the curve loops through it
+        let path = slider("0,0,0,2,0,C|100:0|100:0|200:100,1,0").path();
+        assert!(path.points().iter().any(|(_, y)| *y < 0.0));
+    }
+
+    #[test]
+    fn test_slider_end_position() {
+        assert_eq!(
+            slider("0,0,0,2,0,L|100:0,1,100").end_position(),
+            (100.0, 0.0)
+        );
+        assert_eq!(slider("0,0,0,2,0,L|100:0,2,100").end_position(), (0.0, 0.0));
+    }
+}
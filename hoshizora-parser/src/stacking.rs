@@ -0,0 +1,235 @@
+use crate::{general::Mode, hit_objects::HitObject, time::Time, Beatmap};
+
+/// How close, in osu!pixels, objects must be to stack.
+const STACK_DISTANCE: f64 = 3.0;
+
+/// Where an osu!standard object is drawn after stacking.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stack {
+    /// How many objects the object is stacked on. Negative heights stack down and right, under
+    /// the end of a slider.
+    pub height: i32,
+    /// The object's position moved up and left by a tenth of the circle radius per stack level.
+    pub position: (f64, f64),
+}
+
+/// The stacking of every hit object, in the order of `beatmap.hit_objects`.
+///
+/// Objects stack when they start within `preempt * stack leniency` of the previous one ending and
+/// less than 3 osu!pixels away from it. Beatmaps before v6 use the game's older algorithm, which
+/// compares each object with the ones after it rather than before. Only osu!standard stacks
+/// objects: in other modes, every height is 0.
+pub fn resolve_stacking(beatmap: &Beatmap) -> Vec<Stack> {
+    let timing = beatmap.timing();
+    let objects: Vec<StackObject> = beatmap
+        .hit_objects
+        .iter()
+        .map(|hit_object| {
+            let (position_x, position_y, end_position) = match hit_object {
+                HitObject::HitCircle(t) => (t.position_x, t.position_y, None),
+                HitObject::Slider(t) => (t.position_x, t.position_y, Some(t)),
+                HitObject::Spinner(t) => (t.position_x, t.position_y, None),
+                HitObject::ManiaHold(t) => (t.position_x, t.position_y, None),
+            };
+            let position = (f64::from(position_x), f64::from(position_y));
+
+            StackObject {
+                is_slider: matches!(hit_object, HitObject::Slider(_)),
+                is_spinner: matches!(hit_object, HitObject::Spinner(_)),
+                time: hit_object.time(),
+                end_time: hit_object.end_time(&timing, &beatmap.difficulty),
+                position,
+                end_position: end_position.map_or(position, |t| t.end_position()),
+                path_end_position: end_position.map_or(position, |t| t.path().end_position()),
+            }
+        })
+        .collect();
+
+    let stack_threshold = beatmap.difficulty.preempt() * f64::from(beatmap.general.stack_leniency);
+    let heights = match (beatmap.general.mode, beatmap.format.version) {
+        (Mode::Osu, 6..) => stack_heights(&objects, stack_threshold),
+        (Mode::Osu, _) => legacy_stack_heights(&objects, stack_threshold),
+        _ => vec![0; objects.len()],
+    };
+
+    let offset = -beatmap.difficulty.circle_radius() / 10.0;
+    objects
+        .iter()
+        .zip(heights)
+        .map(|(object, height)| Stack {
+            height,
+            position: (
+                object.position.0 + f64::from(height) * offset,
+                object.position.1 + f64::from(height) * offset,
+            ),
+        })
+        .collect()
+}
+
+/// What stacking needs to know about a hit object.
+struct StackObject {
+    is_slider: bool,
+    is_spinner: bool,
+    time: Time,
+    end_time: Time,
+    position: (f64, f64),
+    /// Where a slider ends after all of its slides.
+    end_position: (f64, f64),
+    /// Where a slider's path ends, whatever the number of slides.
+    path_end_position: (f64, f64),
+}
+
+fn distance((x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    (x1 - x2).hypot(y1 - y2)
+}
+
+/// Walks backwards from each object, stacking earlier objects under it. Circles under the end of
+/// a slider are stacked down and right of it instead.
+fn stack_heights(objects: &[StackObject], stack_threshold: Time) -> Vec<i32> {
+    let mut heights = vec![0; objects.len()];
+
+    for i in (1..objects.len()).rev() {
+        if heights[i] != 0 || objects[i].is_spinner {
+            continue;
+        }
+
+        let mut current = i;
+        if objects[i].is_slider {
+            // from the first slider of a stack, objects always stack up and left
+            for n in (0..i).rev() {
+                if objects[n].is_spinner {
+                    continue;
+                }
+                if objects[current].time - objects[n].time > stack_threshold {
+                    break;
+                }
+                if distance(objects[n].end_position, objects[current].position) < STACK_DISTANCE {
+                    heights[n] = heights[current] + 1;
+                    current = n;
+                }
+            }
+            continue;
+        }
+
+        for n in (0..i).rev() {
+            if objects[n].is_spinner {
+                continue;
+            }
+            if objects[current].time - objects[n].end_time > stack_threshold {
+                break;
+            }
+
+            if objects[n].is_slider
+                && distance(objects[n].end_position, objects[current].position) < STACK_DISTANCE
+            {
+                let offset = heights[current] - heights[n] + 1;
+                for j in n + 1..=i {
+                    if distance(objects[n].end_position, objects[j].position) < STACK_DISTANCE {
+                        heights[j] -= offset;
+                    }
+                }
+                // the slider is stacked from the outer loop, as the base of its own stack
+                break;
+            }
+
+            if distance(objects[n].position, objects[current].position) < STACK_DISTANCE {
+                heights[n] = heights[current] + 1;
+                current = n;
+            }
+        }
+    }
+
+    heights
+}
+
+/// The algorithm of beatmaps before v6: each object is stacked on by the objects after it at its
+/// position, and objects at the end of a slider's path stack down and right.
+fn legacy_stack_heights(objects: &[StackObject], stack_threshold: Time) -> Vec<i32> {
+    let mut heights = vec![0; objects.len()];
+
+    for i in 0..objects.len() {
+        if heights[i] != 0 && !objects[i].is_slider {
+            continue;
+        }
+
+        let mut start_time = objects[i].end_time;
+        let mut slider_stack = 0;
+        for j in i + 1..objects.len() {
+            if objects[j].time - stack_threshold > start_time {
+                break;
+            }
+
+            if distance(objects[j].position, objects[i].position) < STACK_DISTANCE {
+                heights[i] += 1;
+                start_time = objects[j].time;
+            } else if distance(objects[j].position, objects[i].path_end_position) < STACK_DISTANCE {
+                slider_stack += 1;
+                heights[j] -= slider_stack;
+                start_time = objects[j].time;
+            }
+        }
+    }
+
+    heights
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    const TEST_STR: &str = "osu file format v14
+
+        [General]
+        StackLeniency: 0.7
+
+        [Difficulty]
+        CircleSize:4
+        ApproachRate:9
+        SliderMultiplier:1
+
+        [TimingPoints]
+        0,500,4,2,1,60,1,0
+
+        [HitObjects]
+        100,100,1000,1,0
+        100,100,1100,1,0
+        100,100,1200,1,0
+        300,300,5000,2,0,L|400:300,1,100
+        400,300,5600,1,0
+        400,300,5700,1,0";
+
+    #[test]
+    fn test_stacking() {
+        let stacks = parse(TEST_STR).unwrap().stacking();
+        let heights: Vec<i32> = stacks.iter().map(|t| t.height).collect();
+
+        assert_eq!(heights, vec![2, 1, 0, 0, -1, -2]);
+        // a radius of 36.48 moves each level by 3.648 osu!pixels
+        assert!((stacks[0].position.0 - (100.0 - 2.0 * 3.648)).abs() < 1e-9);
+        assert_eq!(stacks[2].position, (100.0, 100.0));
+        assert!((stacks[5].position.1 - (300.0 + 2.0 * 3.648)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_legacy_stacking() {
+        let test_str = TEST_STR.replace("v14", "v5");
+        let heights: Vec<i32> = parse(&test_str)
+            .unwrap()
+            .stacking()
+            .iter()
+            .map(|t| t.height)
+            .collect();
+
+        assert_eq!(heights, vec![2, 1, 0, 0, -1, -2]);
+    }
+
+    #[test]
+    fn test_stacking_other_modes() {
+        let test_str = TEST_STR.replace("StackLeniency: 0.7", "StackLeniency: 0.7\nMode: 2");
+        let stacks = parse(&test_str).unwrap().stacking();
+
+        assert!(stacks.iter().all(|t| t.height == 0));
+        assert_eq!(stacks[1].position, (100.0, 100.0));
+    }
+}
@@ -0,0 +1,1007 @@
+use crate::{
+    error::{default_if_missing, parse_column, ParseError, ParseErrorKind},
+    events::{parse_filename, split_event_params},
+    file_sections::FileSections,
+    format::Format,
+    time::Time,
+};
+use std::{borrow::Cow, fmt, str::FromStr};
+use strum::ParseError::VariantNotFound;
+use strum_macros::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Layer {
+    Background,
+    Fail,
+    Pass,
+    Foreground,
+    Overlay,
+}
+
+impl FromStr for Layer {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" | "Background" => Ok(Self::Background),
+            "1" | "Fail" => Ok(Self::Fail),
+            "2" | "Pass" => Ok(Self::Pass),
+            "3" | "Foreground" => Ok(Self::Foreground),
+            "4" | "Overlay" => Ok(Self::Overlay),
+            _ => Err(VariantNotFound),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Origin {
+    TopLeft,
+    Centre,
+    CentreLeft,
+    TopRight,
+    BottomCentre,
+    TopCentre,
+    Custom,
+    CentreRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Origin {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" | "TopLeft" => Ok(Self::TopLeft),
+            "1" | "Centre" => Ok(Self::Centre),
+            "2" | "CentreLeft" => Ok(Self::CentreLeft),
+            "3" | "TopRight" => Ok(Self::TopRight),
+            "4" | "BottomCentre" => Ok(Self::BottomCentre),
+            "5" | "TopCentre" => Ok(Self::TopCentre),
+            "6" | "Custom" => Ok(Self::Custom),
+            "7" | "CentreRight" => Ok(Self::CentreRight),
+            "8" | "BottomLeft" => Ok(Self::BottomLeft),
+            "9" | "BottomRight" => Ok(Self::BottomRight),
+            _ => Err(VariantNotFound),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Display)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoopType {
+    #[default]
+    LoopForever,
+    LoopOnce,
+}
+
+impl FromStr for LoopType {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "0" | "LoopForever" => Ok(Self::LoopForever),
+            "1" | "LoopOnce" => Ok(Self::LoopOnce),
+            _ => Err(VariantNotFound),
+        }
+    }
+}
+
+/// Easing functions, in the order of their numeric ids.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    #[default]
+    Linear,
+    Out,
+    In,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InQuart,
+    OutQuart,
+    InOutQuart,
+    InQuint,
+    OutQuint,
+    InOutQuint,
+    InSine,
+    OutSine,
+    InOutSine,
+    InExpo,
+    OutExpo,
+    InOutExpo,
+    InCirc,
+    OutCirc,
+    InOutCirc,
+    InElastic,
+    OutElastic,
+    OutElasticHalf,
+    OutElasticQuarter,
+    InOutElastic,
+    InBack,
+    OutBack,
+    InOutBack,
+    InBounce,
+    OutBounce,
+    InOutBounce,
+}
+
+impl Easing {
+    const ALL: [Easing; 35] = [
+        Self::Linear,
+        Self::Out,
+        Self::In,
+        Self::InQuad,
+        Self::OutQuad,
+        Self::InOutQuad,
+        Self::InCubic,
+        Self::OutCubic,
+        Self::InOutCubic,
+        Self::InQuart,
+        Self::OutQuart,
+        Self::InOutQuart,
+        Self::InQuint,
+        Self::OutQuint,
+        Self::InOutQuint,
+        Self::InSine,
+        Self::OutSine,
+        Self::InOutSine,
+        Self::InExpo,
+        Self::OutExpo,
+        Self::InOutExpo,
+        Self::InCirc,
+        Self::OutCirc,
+        Self::InOutCirc,
+        Self::InElastic,
+        Self::OutElastic,
+        Self::OutElasticHalf,
+        Self::OutElasticQuarter,
+        Self::InOutElastic,
+        Self::InBack,
+        Self::OutBack,
+        Self::InOutBack,
+        Self::InBounce,
+        Self::OutBounce,
+        Self::InOutBounce,
+    ];
+}
+
+impl FromStr for Easing {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().parse::<usize>() {
+            Ok(t) => Self::ALL.get(t).copied().ok_or(VariantNotFound),
+            Err(_) => Err(VariantNotFound),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Parameter {
+    FlipHorizontal,
+    FlipVertical,
+    AdditiveBlend,
+}
+
+impl FromStr for Parameter {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "H" => Ok(Self::FlipHorizontal),
+            "V" => Ok(Self::FlipVertical),
+            "A" => Ok(Self::AdditiveBlend),
+            _ => Err(VariantNotFound),
+        }
+    }
+}
+
+/// A value changing from `start_value` to `end_value` between `start_time` and `end_time`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform<T> {
+    pub easing: Easing,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub start_value: T,
+    pub end_value: T,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParameterCommand {
+    pub easing: Easing,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub parameter: Parameter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Loop {
+    pub start_time: Time,
+    pub loop_count: u32,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trigger {
+    pub trigger_name: String,
+    pub start_time: Time,
+    pub end_time: Time,
+    pub group_number: Option<i32>,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Command {
+    Fade(Transform<f32>),
+    Move(Transform<(f32, f32)>),
+    MoveX(Transform<f32>),
+    MoveY(Transform<f32>),
+    Scale(Transform<f32>),
+    VectorScale(Transform<(f32, f32)>),
+    Rotate(Transform<f32>),
+    Colour(Transform<(u8, u8, u8)>),
+    Parameter(ParameterCommand),
+    Loop(Loop),
+    Trigger(Trigger),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sprite {
+    pub layer: Layer,
+    pub origin: Origin,
+    pub filepath: String,
+    pub x: f32,
+    pub y: f32,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Animation {
+    pub layer: Layer,
+    pub origin: Origin,
+    pub filepath: String,
+    pub x: f32,
+    pub y: f32,
+    pub frame_count: u32,
+    pub frame_delay: f64,
+    pub loop_type: LoopType,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sample {
+    pub time: Time,
+    pub layer: Layer,
+    pub filepath: String,
+    pub volume: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StoryboardObject {
+    Sprite(Sprite),
+    Animation(Animation),
+    Sample(Sample),
+}
+
+impl StoryboardObject {
+    pub fn layer(&self) -> Layer {
+        match self {
+            Self::Sprite(t) => t.layer,
+            Self::Animation(t) => t.layer,
+            Self::Sample(t) => t.layer,
+        }
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        match self {
+            Self::Sprite(t) => &t.commands,
+            Self::Animation(t) => &t.commands,
+            Self::Sample(_) => &[],
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Storyboard {
+    /// `$name=value` pairs from the `[Variables]` section, in declaration order.
+    pub variables: Vec<(String, String)>,
+    /// Objects in the order they were declared, which is also their drawing order within a layer.
+    pub objects: Vec<StoryboardObject>,
+}
+
+impl Storyboard {
+    pub fn new(variables: Vec<(String, String)>, objects: Vec<StoryboardObject>) -> Self {
+        Self { variables, objects }
+    }
+
+    /// Combines a mapset's shared `.osb` storyboard with a difficulty's own `[Events]`
+    /// storyboard. The difficulty's objects are drawn above the shared ones on every layer, and
+    /// a variable declared by both keeps the shared storyboard's value.
+    pub fn merged(shared: &Storyboard, difficulty: &Storyboard) -> Self {
+        let mut variables = shared.variables.clone();
+        for (name, value) in &difficulty.variables {
+            if !variables.iter().any(|(t, _)| t == name) {
+                variables.push((name.clone(), value.clone()));
+            }
+        }
+
+        Self {
+            variables,
+            objects: [&shared.objects[..], &difficulty.objects[..]].concat(),
+        }
+    }
+
+    /// Substitutes every comma-separated field of `line` that is a declared `$name` with its
+    /// value. Variables used in values are expanded in turn, so expanding twice changes nothing.
+    pub fn expand_variables<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.variables.is_empty() || !line.contains('$') {
+            return Cow::Borrowed(line);
+        }
+
+        Cow::Owned(self.expand_fields(line, &mut Vec::new()))
+    }
+
+    /// `expanding` holds the variables whose values are being expanded: one used in its own value
+    /// is left as written.
+    fn expand_fields<'s>(&'s self, text: &str, expanding: &mut Vec<&'s str>) -> String {
+        let fields: Vec<String> = text
+            .split(',')
+            .map(|field| {
+                let name = field.trim();
+                match self.variables.iter().find(|(t, _)| t == name) {
+                    Some((name, value)) if !expanding.contains(&name.as_str()) => {
+                        expanding.push(name);
+                        let value = self.expand_fields(value, expanding);
+                        expanding.pop();
+                        let indent = &field[..field.len() - field.trim_start().len()];
+                        format!("{}{}", indent, value)
+                    }
+                    _ => String::from(field),
+                }
+            })
+            .collect();
+
+        fields.join(",")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// The objects drawn on `layer`, in drawing order.
+    pub fn layer(&self, layer: Layer) -> impl Iterator<Item = &StoryboardObject> {
+        self.objects.iter().filter(move |t| t.layer() == layer)
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FlipHorizontal => write!(f, "H"),
+            Self::FlipVertical => write!(f, "V"),
+            Self::AdditiveBlend => write!(f, "A"),
+        }
+    }
+}
+
+impl Command {
+    /// Writes the command as a line indented by `depth`, followed by its nested commands.
+    fn write(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = " ".repeat(depth);
+        let header = |f: &mut fmt::Formatter<'_>, code: &str, easing: Easing, start, end| {
+            write!(f, "{}{},{},{},{}", indent, code, easing as u8, start, end)
+        };
+
+        match self {
+            Command::Fade(t)
+            | Command::MoveX(t)
+            | Command::MoveY(t)
+            | Command::Scale(t)
+            | Command::Rotate(t) => {
+                let code = match self {
+                    Command::Fade(_) => "F",
+                    Command::MoveX(_) => "MX",
+                    Command::MoveY(_) => "MY",
+                    Command::Scale(_) => "S",
+                    _ => "R",
+                };
+                header(f, code, t.easing, t.start_time, t.end_time)?;
+                writeln!(f, ",{},{}", t.start_value, t.end_value)
+            }
+            Command::Move(t) | Command::VectorScale(t) => {
+                let code = match self {
+                    Command::Move(_) => "M",
+                    _ => "V",
+                };
+                header(f, code, t.easing, t.start_time, t.end_time)?;
+                writeln!(
+                    f,
+                    ",{},{},{},{}",
+                    t.start_value.0, t.start_value.1, t.end_value.0, t.end_value.1
+                )
+            }
+            Command::Colour(t) => {
+                header(f, "C", t.easing, t.start_time, t.end_time)?;
+                let (r, g, b) = t.start_value;
+                let (end_r, end_g, end_b) = t.end_value;
+                writeln!(f, ",{},{},{},{},{},{}", r, g, b, end_r, end_g, end_b)
+            }
+            Command::Parameter(t) => {
+                header(f, "P", t.easing, t.start_time, t.end_time)?;
+                writeln!(f, ",{}", t.parameter)
+            }
+            Command::Loop(t) => {
+                writeln!(f, "{}L,{},{}", indent, t.start_time, t.loop_count)?;
+                t.commands.iter().try_for_each(|c| c.write(f, depth + 1))
+            }
+            Command::Trigger(t) => {
+                write!(
+                    f,
+                    "{}T,{},{},{}",
+                    indent, t.trigger_name, t.start_time, t.end_time
+                )?;
+                match t.group_number {
+                    Some(group_number) => writeln!(f, ",{}", group_number)?,
+                    None => writeln!(f)?,
+                }
+                t.commands.iter().try_for_each(|c| c.write(f, depth + 1))
+            }
+        }
+    }
+}
+
+impl fmt::Display for StoryboardObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sprite(t) => writeln!(
+                f,
+                "Sprite,{},{},\"{}\",{},{}",
+                t.layer, t.origin, t.filepath, t.x, t.y
+            )?,
+            Self::Animation(t) => writeln!(
+                f,
+                "Animation,{},{},\"{}\",{},{},{},{},{}",
+                t.layer, t.origin, t.filepath, t.x, t.y, t.frame_count, t.frame_delay, t.loop_type
+            )?,
+            Self::Sample(t) => writeln!(
+                f,
+                "Sample,{},{},\"{}\",{}",
+                t.time, t.layer as u8, t.filepath, t.volume
+            )?,
+        }
+
+        self.commands().iter().try_for_each(|c| c.write(f, 1))
+    }
+}
+
+/// Writes the `[Events]` lines of the storyboard's objects. Variables are already expanded.
+impl fmt::Display for Storyboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.objects.iter().try_for_each(|t| write!(f, "{}", t))
+    }
+}
+
+/// Parses a standalone `.osb` storyboard, reading the objects from its `[Events]` section.
+pub fn parse_storyboard(text: &str) -> Result<Storyboard, ParseError> {
+    let mut storyboard = Storyboard::default();
+    let mut current_section = FileSections::None;
+
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if FileSections::is_header(line) {
+            current_section = FileSections::get_section(line);
+            continue;
+        }
+        let result = match current_section {
+            FileSections::Variables => parse_variables(line, &mut storyboard),
+            FileSections::Events => {
+                let line = storyboard.expand_variables(line);
+                if is_storyboard_line(&line) {
+                    parse_storyboard_line(&line, &mut storyboard, &Format::default())
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        };
+        result.map_err(|e| e.with_line(index + 1))?;
+    }
+
+    Ok(storyboard)
+}
+
+/// Parses a `$name=value` line of the `[Variables]` section.
+pub fn parse_variables(line: &str, storyboard: &mut Storyboard) -> Result<(), ParseError> {
+    let (name, value) = line
+        .split_once('=')
+        .ok_or_else(|| ParseError::missing_separator(FileSections::Variables, line))?;
+    let name = name.trim();
+    if !name.starts_with('$') {
+        return Err(ParseError::invalid_key(FileSections::Variables, line, name));
+    }
+
+    match storyboard.variables.iter_mut().find(|(t, _)| t == name) {
+        Some((_, t)) => *t = String::from(value.trim()),
+        None => storyboard
+            .variables
+            .push((String::from(name), String::from(value.trim()))),
+    }
+
+    Ok(())
+}
+
+/// Whether an `[Events]` line declares a storyboard object or one of its commands.
+pub fn is_storyboard_line(line: &str) -> bool {
+    line.starts_with(' ')
+        || line.starts_with('_')
+        || matches!(
+            line.split(',').next().map(str::trim),
+            Some("4" | "5" | "6" | "Sprite" | "Sample" | "Animation")
+        )
+}
+
+/// Parses a single storyboard line, attaching commands to the most recently declared object.
+///
+/// Like the rest of the file, times before v5 are shifted by [`Format::offset`], except those of
+/// commands inside loops and triggers, which are relative to the start of their group.
+pub fn parse_storyboard_line(
+    line: &str,
+    storyboard: &mut Storyboard,
+    format: &Format,
+) -> Result<(), ParseError> {
+    let depth = line.chars().take_while(|c| *c == ' ' || *c == '_').count();
+    let params = split_event_params(&line[depth..]);
+    let column = |index| move |kind| ParseError::column(FileSections::Events, line, index, kind);
+
+    if depth == 0 {
+        let object = match params[0] {
+            "4" | "Sprite" => StoryboardObject::Sprite(Sprite {
+                layer: parse_column(&params, 1).map_err(column(1))?,
+                origin: parse_column(&params, 2).map_err(column(2))?,
+                filepath: parse_filename(&params, 3).map_err(column(3))?,
+                x: parse_column(&params, 4).map_err(column(4))?,
+                y: parse_column(&params, 5).map_err(column(5))?,
+                commands: Vec::new(),
+            }),
+            "6" | "Animation" => StoryboardObject::Animation(Animation {
+                layer: parse_column(&params, 1).map_err(column(1))?,
+                origin: parse_column(&params, 2).map_err(column(2))?,
+                filepath: parse_filename(&params, 3).map_err(column(3))?,
+                x: parse_column(&params, 4).map_err(column(4))?,
+                y: parse_column(&params, 5).map_err(column(5))?,
+                frame_count: parse_column(&params, 6).map_err(column(6))?,
+                frame_delay: parse_column(&params, 7).map_err(column(7))?,
+                loop_type: parse_column(&params, 8)
+                    .or_else(default_if_missing)
+                    .map_err(column(8))?,
+                commands: Vec::new(),
+            }),
+            "5" | "Sample" => StoryboardObject::Sample(Sample {
+                time: parse_column::<Time>(&params, 1).map_err(column(1))? + format.offset(),
+                layer: parse_column(&params, 2).map_err(column(2))?,
+                filepath: parse_filename(&params, 3).map_err(column(3))?,
+                volume: match params.get(4) {
+                    Some(_) => parse_column(&params, 4).map_err(column(4))?,
+                    None => 100,
+                },
+            }),
+            _ => return Err(column(0)(ParseErrorKind::InvalidValue)),
+        };
+        storyboard.objects.push(object);
+
+        return Ok(());
+    }
+
+    let commands = match storyboard.objects.last_mut() {
+        Some(StoryboardObject::Sprite(t)) => &mut t.commands,
+        Some(StoryboardObject::Animation(t)) => &mut t.commands,
+        _ => return Err(column(0)(ParseErrorKind::InvalidValue)),
+    };
+    let commands = match (depth, commands.last_mut()) {
+        (1, _) => commands,
+        (2, Some(Command::Loop(t))) => &mut t.commands,
+        (2, Some(Command::Trigger(t))) => &mut t.commands,
+        _ => return Err(column(0)(ParseErrorKind::InvalidValue)),
+    };
+    // loops and triggers can't be nested inside each other
+    if depth == 2 && matches!(params[0], "L" | "T") {
+        return Err(column(0)(ParseErrorKind::InvalidValue));
+    }
+
+    let offset = match depth {
+        1 => format.offset(),
+        _ => Time::ZERO,
+    };
+    commands.extend(parse_commands(&params, offset).map_err(|(index, kind)| column(index)(kind))?);
+
+    Ok(())
+}
+
+/// Parses a command, expanding the shorthand for consecutive transforms into one command each.
+/// `offset` is added to every time of the command.
+fn parse_commands(params: &[&str], offset: Time) -> Result<Vec<Command>, (usize, ParseErrorKind)> {
+    let column = |index| move |kind| (index, kind);
+    let time = |index| {
+        parse_column::<Time>(params, index)
+            .map(|t| t + offset)
+            .map_err(column(index))
+    };
+
+    match params[0] {
+        "L" => {
+            return Ok(vec![Command::Loop(Loop {
+                start_time: time(1)?,
+                loop_count: parse_column(params, 2).map_err(column(2))?,
+                commands: Vec::new(),
+            })])
+        }
+        "T" => {
+            return Ok(vec![Command::Trigger(Trigger {
+                trigger_name: params
+                    .get(1)
+                    .map(|t| String::from(*t))
+                    .ok_or(ParseErrorKind::MissingField)
+                    .map_err(column(1))?,
+                start_time: time(2)?,
+                end_time: time(3)?,
+                group_number: match params.get(4) {
+                    Some(_) => Some(parse_column(params, 4).map_err(column(4))?),
+                    None => None,
+                },
+                commands: Vec::new(),
+            })])
+        }
+        _ => {}
+    }
+
+    let easing = parse_column(params, 1).map_err(column(1))?;
+    let start_time = time(2)?;
+    let end_time = match params.get(3) {
+        Some(&"") => start_time,
+        _ => time(3)?,
+    };
+
+    let commands = match params[0] {
+        "F" => transforms(params, 1, easing, start_time, end_time, |t: &[f32]| t[0])?
+            .into_iter()
+            .map(Command::Fade)
+            .collect(),
+        "M" => transforms(params, 2, easing, start_time, end_time, |t: &[f32]| {
+            (t[0], t[1])
+        })?
+        .into_iter()
+        .map(Command::Move)
+        .collect(),
+        "MX" => transforms(params, 1, easing, start_time, end_time, |t: &[f32]| t[0])?
+            .into_iter()
+            .map(Command::MoveX)
+            .collect(),
+        "MY" => transforms(params, 1, easing, start_time, end_time, |t: &[f32]| t[0])?
+            .into_iter()
+            .map(Command::MoveY)
+            .collect(),
+        "S" => transforms(params, 1, easing, start_time, end_time, |t: &[f32]| t[0])?
+            .into_iter()
+            .map(Command::Scale)
+            .collect(),
+        "V" => transforms(params, 2, easing, start_time, end_time, |t: &[f32]| {
+            (t[0], t[1])
+        })?
+        .into_iter()
+        .map(Command::VectorScale)
+        .collect(),
+        "R" => transforms(params, 1, easing, start_time, end_time, |t: &[f32]| t[0])?
+            .into_iter()
+            .map(Command::Rotate)
+            .collect(),
+        "C" => transforms(params, 3, easing, start_time, end_time, |t: &[u8]| {
+            (t[0], t[1], t[2])
+        })?
+        .into_iter()
+        .map(Command::Colour)
+        .collect(),
+        "P" => vec![Command::Parameter(ParameterCommand {
+            easing,
+            start_time,
+            end_time,
+            parameter: parse_column(params, 4).map_err(column(4))?,
+        })],
+        _ => return Err((0, ParseErrorKind::InvalidValue)),
+    };
+
+    Ok(commands)
+}
+
+/// Reads the values after the end time in groups of `arity`. A single group means the value
+/// doesn't change, and every further group starts another transform of the same duration where
+/// the previous one ended.
+fn transforms<V: FromStr + Copy, T: Clone>(
+    params: &[&str],
+    arity: usize,
+    easing: Easing,
+    start_time: Time,
+    end_time: Time,
+    value: impl Fn(&[V]) -> T,
+) -> Result<Vec<Transform<T>>, (usize, ParseErrorKind)> {
+    let values = (4..params.len().max(4 + arity))
+        .map(|index| parse_column::<V>(params, index).map_err(|kind| (index, kind)))
+        .collect::<Result<Vec<V>, _>>()?;
+    if values.len() % arity != 0 {
+        return Err((params.len(), ParseErrorKind::MissingField));
+    }
+
+    let values: Vec<T> = values.chunks(arity).map(value).collect();
+    if values.len() == 1 {
+        return Ok(vec![Transform {
+            easing,
+            start_time,
+            end_time,
+            start_value: values[0].clone(),
+            end_value: values[0].clone(),
+        }]);
+    }
+
+    let duration = end_time - start_time;
+    Ok(values
+        .windows(2)
+        .enumerate()
+        .map(|(i, t)| Transform {
+            easing,
+            start_time: start_time + duration * i as f64,
+            end_time: end_time + duration * i as f64,
+            start_value: t[0].clone(),
+            end_value: t[1].clone(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_storyboard() {
+        let test_str = "[Events]
+//Storyboard Layer 0 (Background)
+Sprite,Background,Centre,\"sb/bg.jpg\",320,240
+ F,0,1000,2000,0,1,0
+ M,1,1000,,320,240
+ L,3000,4
+  R,0,0,500,0,2.5
+ T,HitSoundClap,0,10000,1
+  C,0,0,100,255,0,0,255,255,255
+//Storyboard Layer 3 (Foreground)
+Animation,Foreground,TopLeft,\"sb/anim.png\",0,0,4,50,LoopOnce
+ P,0,0,1000,A
+//Storyboard Sound Samples
+Sample,500,3,\"sb/ding.wav\",60";
+        let storyboard = parse_storyboard(test_str).unwrap();
+
+        assert_eq!(
+            storyboard.objects[0],
+            StoryboardObject::Sprite(Sprite {
+                layer: Layer::Background,
+                origin: Origin::Centre,
+                filepath: String::from("sb/bg.jpg"),
+                x: 320.0,
+                y: 240.0,
+                commands: vec![
+                    Command::Fade(Transform {
+                        easing: Easing::Linear,
+                        start_time: Time::from(1000),
+                        end_time: Time::from(2000),
+                        start_value: 0.0,
+                        end_value: 1.0,
+                    }),
+                    Command::Fade(Transform {
+                        easing: Easing::Linear,
+                        start_time: Time::from(2000),
+                        end_time: Time::from(3000),
+                        start_value: 1.0,
+                        end_value: 0.0,
+                    }),
+                    Command::Move(Transform {
+                        easing: Easing::Out,
+                        start_time: Time::from(1000),
+                        end_time: Time::from(1000),
+                        start_value: (320.0, 240.0),
+                        end_value: (320.0, 240.0),
+                    }),
+                    Command::Loop(Loop {
+                        start_time: Time::from(3000),
+                        loop_count: 4,
+                        commands: vec![Command::Rotate(Transform {
+                            easing: Easing::Linear,
+                            start_time: Time::from(0),
+                            end_time: Time::from(500),
+                            start_value: 0.0,
+                            end_value: 2.5,
+                        })],
+                    }),
+                    Command::Trigger(Trigger {
+                        trigger_name: String::from("HitSoundClap"),
+                        start_time: Time::from(0),
+                        end_time: Time::from(10000),
+                        group_number: Some(1),
+                        commands: vec![Command::Colour(Transform {
+                            easing: Easing::Linear,
+                            start_time: Time::from(0),
+                            end_time: Time::from(100),
+                            start_value: (255, 0, 0),
+                            end_value: (255, 255, 255),
+                        })],
+                    }),
+                ],
+            })
+        );
+        assert_eq!(
+            storyboard.objects[1],
+            StoryboardObject::Animation(Animation {
+                layer: Layer::Foreground,
+                origin: Origin::TopLeft,
+                filepath: String::from("sb/anim.png"),
+                x: 0.0,
+                y: 0.0,
+                frame_count: 4,
+                frame_delay: 50.0,
+                loop_type: LoopType::LoopOnce,
+                commands: vec![Command::Parameter(ParameterCommand {
+                    easing: Easing::Linear,
+                    start_time: Time::from(0),
+                    end_time: Time::from(1000),
+                    parameter: Parameter::AdditiveBlend,
+                })],
+            })
+        );
+        assert_eq!(
+            storyboard.objects[2],
+            StoryboardObject::Sample(Sample {
+                time: Time::from(500),
+                layer: Layer::Foreground,
+                filepath: String::from("sb/ding.wav"),
+                volume: 60,
+            })
+        );
+        assert_eq!(storyboard.layer(Layer::Foreground).count(), 2);
+    }
+
+    #[test]
+    fn test_parse_storyboard_orphan_command() {
+        let test_str = "[Events]\n F,0,1000,2000,0,1";
+
+        assert_eq!(
+            parse_storyboard(test_str),
+            Err(ParseError::column(
+                FileSections::Events,
+                " F,0,1000,2000,0,1",
+                0,
+                ParseErrorKind::InvalidValue
+            )
+            .with_line(2))
+        );
+    }
+
+    #[test]
+    fn test_parse_storyboard_variables() {
+        let test_str = "[Variables]
+$layer=Foreground
+$l=Background
+$pos=320,240
+
+[Events]
+Sprite,$layer,Centre,\"sb/a.png\",$pos
+Sprite,$l,Centre,\"sb/b.png\",$pos";
+        let storyboard = parse_storyboard(test_str).unwrap();
+
+        assert_eq!(storyboard.objects[0].layer(), Layer::Foreground);
+        assert_eq!(storyboard.objects[1].layer(), Layer::Background);
+        assert_eq!(
+            storyboard.expand_variables("M,0,0,,$pos"),
+            Cow::<str>::Owned(String::from("M,0,0,,320,240"))
+        );
+        // only whole fields are variables
+        assert_eq!(
+            storyboard.expand_variables("Sprite,$layer,Centre,\"sb/$l.png\",$posx"),
+            Cow::<str>::Owned(String::from("Sprite,Foreground,Centre,\"sb/$l.png\",$posx"))
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_storyboard() {
+        let test_str = [
+            "osu file format v4",
+            "",
+            "[Events]",
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " F,0,1000,2000,0,1",
+            " L,3000,2",
+            "  F,0,0,500,1,0",
+            "Sample,500,3,\"sb/ding.wav\",60",
+        ]
+        .join("\n");
+        let storyboard = crate::parse(&test_str).unwrap().storyboard;
+
+        let commands = storyboard.objects[0].commands();
+        assert!(matches!(
+            &commands[0],
+            Command::Fade(t) if t.start_time == Time::from(1024) && t.end_time == Time::from(2024)
+        ));
+        // commands in a loop stay relative to its start
+        assert!(matches!(
+            &commands[1],
+            Command::Loop(t) if t.start_time == Time::from(3024)
+                && matches!(&t.commands[0], Command::Fade(t) if t.start_time == Time::ZERO)
+        ));
+        assert!(matches!(
+            &storyboard.objects[1],
+            StoryboardObject::Sample(t) if t.time == Time::from(524)
+        ));
+    }
+
+    #[test]
+    fn test_merged_storyboard() {
+        let shared = parse_storyboard(
+            "[Events]\nSprite,Foreground,Centre,\"shared.png\",0,0\nSample,0,0,\"a.wav\"",
+        )
+        .unwrap();
+        let difficulty =
+            parse_storyboard("[Events]\nSprite,Foreground,Centre,\"diff.png\",0,0").unwrap();
+        let merged = Storyboard::merged(&shared, &difficulty);
+
+        let filepaths: Vec<&str> = merged
+            .layer(Layer::Foreground)
+            .filter_map(|t| match t {
+                StoryboardObject::Sprite(sprite) => Some(sprite.filepath.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(filepaths, vec!["shared.png", "diff.png"]);
+        assert_eq!(merged.objects.len(), 3);
+
+        let shared = parse_storyboard("[Variables]\n$a=1\n$b=2").unwrap();
+        let difficulty = parse_storyboard("[Variables]\n$b=3\n$c=4").unwrap();
+        assert_eq!(
+            Storyboard::merged(&shared, &difficulty).variables,
+            vec![
+                (String::from("$a"), String::from("1")),
+                (String::from("$b"), String::from("2")),
+                (String::from("$c"), String::from("4")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_storyboard() {
+        let test_str = "[Events]
+Sprite,Background,Centre,\"sb/bg.jpg\",320,240
+ F,0,1000,2000,0,1
+ L,3000,4
+  M,1,0,500,0,0,10,10
+ T,HitSoundClap,0,10000
+  C,0,0,100,255,0,0,255,255,255
+Sample,500,3,\"sb/ding.wav\",60
+";
+        let storyboard = parse_storyboard(test_str).unwrap();
+
+        assert_eq!(format!("[Events]\n{}", storyboard), test_str);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    error::{split_key_value, ParseError},
+    events::{parse_event, Event},
+    file_sections::FileSections,
+    format::{parse_format_line, Format},
+    hit_objects::{parse_hit_object_ref, HitObject},
+    storyboard::{
+        is_storyboard_line, parse_storyboard_line, parse_variables, Storyboard, StoryboardObject,
+    },
+    timing_points::{parse_timing_point, TimingPoint},
+};
+use std::{iter::Enumerate, str::Lines};
+
+/// An item of a beatmap, yielded by [`BeatmapEvents`].
+///
+/// Items come in file order, except for storyboard objects: the game keeps adding commands to the
+/// last object until the next one or the end of `[Events]`, even past other events, so an object
+/// is yielded after the events that follow it in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BeatmapItem<'a> {
+    /// The `osu file format vN` line.
+    Format(Format),
+    /// A `[Name]` header. `section` is [`FileSections::None`] for sections this crate doesn't
+    /// know.
+    SectionStart {
+        section: FileSections,
+        name: &'a str,
+    },
+    /// A line of `[General]`, `[Editor]`, `[Metadata]`, `[Difficulty]`, `[Colours]` or
+    /// `[Variables]`. Values are only checked when they're applied to a beatmap.
+    KeyValue {
+        section: FileSections,
+        key: &'a str,
+        value: &'a str,
+    },
+    Event(Event),
+    /// A storyboard object from `[Events]`, yielded once all of its commands have been read.
+    StoryboardObject(StoryboardObject),
+    TimingPoint(TimingPoint),
+    /// A hit object whose sample filename is borrowed from the line.
+    HitObject(HitObject<&'a str>),
+    /// A line of an unknown section, or before the first header.
+    UnknownLine(&'a str),
+}
+
+/// Parses a beatmap one item at a time, without building a [`Beatmap`](crate::Beatmap).
+///
+/// Memory use doesn't grow with the size of the file: only the storyboard object currently being
+/// read and the storyboard variables are kept between items. A line that fails to parse yields an
+/// error and parsing resumes with the next one.
+#[derive(Debug, Clone)]
+pub struct BeatmapEvents<'a, I = Lines<'a>> {
+    lines: Enumerate<I>,
+    section: FileSections,
+    format: Format,
+    /// The `[Variables]` used to expand `[Events]` lines, and the storyboard object whose commands
+    /// are still being read.
+    storyboard: Storyboard,
+    queued: Option<BeatmapItem<'a>>,
+    line_number: usize,
+    line: &'a str,
+}
+
+impl<'a> BeatmapEvents<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self::from_lines(text.lines())
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a str>> BeatmapEvents<'a, I> {
+    /// Reads the lines of a document that has already been split, without their line endings.
+    pub(crate) fn from_lines(lines: I) -> Self {
+        Self {
+            lines: lines.enumerate(),
+            section: FileSections::None,
+            format: Format::default(),
+            storyboard: Storyboard::default(),
+            queued: None,
+            line_number: 0,
+            line: "",
+        }
+    }
+
+    /// The 1-based number of the line the last item was read from.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// The text of the line the last item was read from.
+    pub fn line(&self) -> &'a str {
+        self.line
+    }
+
+    fn parse_line(&mut self, line: &'a str) -> Result<Option<BeatmapItem<'a>>, ParseError> {
+        if FileSections::is_header(line) {
+            self.section = FileSections::get_section(line);
+            if self.section == FileSections::Format {
+                self.format = parse_format_line(line)?;
+                return Ok(Some(BeatmapItem::Format(self.format.clone())));
+            }
+
+            let start = BeatmapItem::SectionStart {
+                section: self.section,
+                name: line.trim().trim_start_matches('[').trim_end_matches(']'),
+            };
+            // the last storyboard object has no more commands coming
+            return Ok(Some(match self.storyboard.objects.pop() {
+                Some(object) => {
+                    self.queued = Some(start);
+                    BeatmapItem::StoryboardObject(object)
+                }
+                None => start,
+            }));
+        }
+
+        let item = match self.section {
+            FileSections::Format => {
+                self.format = parse_format_line(line)?;
+                BeatmapItem::Format(self.format.clone())
+            }
+            FileSections::General
+            | FileSections::Editor
+            | FileSections::Metadata
+            | FileSections::Difficulty
+            | FileSections::Colours => {
+                let (key, value) = split_key_value(line, self.section)?;
+                BeatmapItem::KeyValue {
+                    section: self.section,
+                    key,
+                    value,
+                }
+            }
+            FileSections::Variables => {
+                parse_variables(line, &mut self.storyboard)?;
+                let (key, value) = line.split_once('=').unwrap_or((line, ""));
+                BeatmapItem::KeyValue {
+                    section: self.section,
+                    key: key.trim(),
+                    value: value.trim(),
+                }
+            }
+            FileSections::Events => {
+                let line = self.storyboard.expand_variables(line);
+                if !is_storyboard_line(&line) {
+                    return Ok(parse_event(&line, &self.format)?.map(BeatmapItem::Event));
+                }
+
+                parse_storyboard_line(&line, &mut self.storyboard, &self.format)?;
+                match self.storyboard.objects.len() {
+                    2 => BeatmapItem::StoryboardObject(self.storyboard.objects.remove(0)),
+                    _ => return Ok(None),
+                }
+            }
+            FileSections::TimingPoints => {
+                BeatmapItem::TimingPoint(parse_timing_point(line, &self.format)?)
+            }
+            FileSections::HitObjects => {
+                BeatmapItem::HitObject(parse_hit_object_ref(line, &self.format)?)
+            }
+            FileSections::None => BeatmapItem::UnknownLine(line.trim()),
+        };
+
+        Ok(Some(item))
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Iterator for BeatmapEvents<'a, I> {
+    type Item = Result<BeatmapItem<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.queued.take() {
+            return Some(Ok(item));
+        }
+
+        while let Some((index, line)) = self.lines.next() {
+            if line.trim().is_empty() || line.starts_with("//") {
+                continue;
+            }
+            self.line_number = index + 1;
+            self.line = line;
+
+            match self.parse_line(line) {
+                Ok(Some(item)) => return Some(Ok(item)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e.with_line(index + 1))),
+            }
+        }
+
+        self.storyboard
+            .objects
+            .pop()
+            .map(|object| Ok(BeatmapItem::StoryboardObject(object)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseErrorKind;
+
+    #[test]
+    fn test_beatmap_events() {
+        let test_str = [
+            "osu file format v14",
+            "",
+            "[General]",
+            "AudioFilename: audio.mp3",
+            "",
+            "[Events]",
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " F,0,0,1000,0,1",
+            "2,10000,12000",
+            " M,0,0,1000,320,240",
+            "",
+            "[TimingPoints]",
+            "0,500,4,2,1,35,1,0",
+            "abc",
+            "",
+            "[HitObjects]",
+            "256,192,11000,21,2",
+        ]
+        .join("\n");
+        let items: Vec<_> = BeatmapEvents::new(&test_str).collect();
+
+        assert_eq!(items.len(), 11);
+        assert_eq!(items[0], Ok(BeatmapItem::Format(Format::new(14))));
+        assert_eq!(
+            items[2],
+            Ok(BeatmapItem::KeyValue {
+                section: FileSections::General,
+                key: "AudioFilename",
+                value: "audio.mp3",
+            })
+        );
+        // the sprite is held back until `[Events]` ends, since the command after the break is
+        // still one of its own
+        assert!(matches!(items[4], Ok(BeatmapItem::Event(Event::Break(_)))));
+        assert!(
+            matches!(&items[5], Ok(BeatmapItem::StoryboardObject(t)) if t.commands().len() == 2)
+        );
+        assert_eq!(
+            items[6],
+            Ok(BeatmapItem::SectionStart {
+                section: FileSections::TimingPoints,
+                name: "TimingPoints",
+            })
+        );
+        assert!(matches!(items[7], Ok(BeatmapItem::TimingPoint(_))));
+        assert!(matches!(
+            &items[8],
+            Err(ParseError {
+                line: 14,
+                kind: ParseErrorKind::InvalidValue,
+                ..
+            })
+        ));
+        assert!(matches!(items[10], Ok(BeatmapItem::HitObject(_))));
+    }
+}
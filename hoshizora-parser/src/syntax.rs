@@ -0,0 +1,494 @@
+use crate::{
+    apply_item, file_sections::FileSections, fold_items, Beatmap, ParseError, ParseOptions,
+};
+use std::{fmt, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// Spaces and tabs around other tokens.
+    Whitespace,
+    /// A `// comment` line.
+    Comment,
+    /// A `[Section]` or `osu file format vN` line.
+    Header,
+    Key,
+    /// The `:` of a `Key: value` line, or the `=` of a `$name=value` line.
+    Separator,
+    Value,
+    /// The leading spaces or underscores of a nested storyboard command.
+    Indent,
+    /// A column of a comma-separated line.
+    Field,
+    Comma,
+    /// Anything that doesn't fit the grammar of its section, kept verbatim.
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range of the token in the text of its line.
+    pub span: Range<usize>,
+}
+
+/// A single line of the document, split into tokens that cover all of its text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    section: FileSections,
+    text: String,
+    ending: String,
+    tokens: Vec<Token>,
+}
+
+impl Line {
+    fn new(section: FileSections, text: &str, ending: &str) -> Self {
+        Self {
+            section,
+            text: String::from(text),
+            ending: String::from(ending),
+            tokens: tokenize(section, text),
+        }
+    }
+
+    /// The text of the line, without its line ending.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The line ending: `"\n"`, `"\r\n"`, or empty for the last line of the document.
+    pub fn ending(&self) -> &str {
+        &self.ending
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn token_text(&self, token: &Token) -> &str {
+        &self.text[token.span.clone()]
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        self.find(TokenKind::Key).map(|t| self.token_text(t))
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.find(TokenKind::Value).map(|t| self.token_text(t))
+    }
+
+    /// The columns of a comma-separated line, as written.
+    pub fn fields(&self) -> Vec<&str> {
+        self.tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Field)
+            .map(|t| self.token_text(t))
+            .collect()
+    }
+
+    /// Replaces the value of a `Key: value` line, keeping the key and surrounding whitespace.
+    pub fn set_value(&mut self, value: &str) -> bool {
+        match self.find(TokenKind::Value) {
+            Some(t) => {
+                let span = t.span.clone();
+                self.replace_span(span, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the column at `index` of a comma-separated line.
+    pub fn set_field(&mut self, index: usize, value: &str) -> bool {
+        let span = self
+            .tokens
+            .iter()
+            .filter(|t| t.kind == TokenKind::Field)
+            .nth(index)
+            .map(|t| t.span.clone());
+        match span {
+            Some(span) => {
+                self.replace_span(span, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the whole text of the line, keeping its line ending.
+    pub fn set_text(&mut self, text: &str) {
+        self.text = String::from(text);
+        self.tokens = tokenize(self.section, text);
+    }
+
+    fn find(&self, kind: TokenKind) -> Option<&Token> {
+        self.tokens.iter().find(|t| t.kind == kind)
+    }
+
+    fn replace_span(&mut self, span: Range<usize>, value: &str) {
+        let mut text = self.text.clone();
+        text.replace_range(span, value);
+        self.set_text(&text);
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.text, self.ending)
+    }
+}
+
+/// A section header and the lines up to the next one. The lines before the first header form a
+/// section of their own, without a header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionNode {
+    section: FileSections,
+    header: Option<Line>,
+    lines: Vec<Line>,
+}
+
+impl SectionNode {
+    /// The section, or [`FileSections::None`] for unknown sections and the lines before the
+    /// first header.
+    pub fn section(&self) -> FileSections {
+        self.section
+    }
+
+    pub fn header(&self) -> Option<&Line> {
+        self.header.as_ref()
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    pub fn lines_mut(&mut self) -> &mut [Line] {
+        &mut self.lines
+    }
+
+    pub fn find_key(&self, key: &str) -> Option<&Line> {
+        self.lines.iter().find(|t| t.key() == Some(key))
+    }
+
+    pub fn find_key_mut(&mut self, key: &str) -> Option<&mut Line> {
+        self.lines.iter_mut().find(|t| t.key() == Some(key))
+    }
+
+    /// Appends a line after the last non-blank line of the section.
+    pub fn push_line(&mut self, text: &str) {
+        let index = self
+            .lines
+            .iter()
+            .rposition(|t| !t.is_blank())
+            .map_or(0, |t| t + 1);
+        let ending = self
+            .lines
+            .iter()
+            .chain(self.header.iter())
+            .map(|t| t.ending())
+            .find(|t| !t.is_empty())
+            .map_or_else(|| String::from("\n"), String::from);
+
+        // if the section ends the document, the new line takes over its missing ending
+        let previous = match index.checked_sub(1) {
+            Some(t) => Some(&mut self.lines[t]),
+            None => self.header.as_mut(),
+        };
+        let ending = match previous {
+            Some(previous) if previous.ending.is_empty() => {
+                previous.ending = ending;
+                String::new()
+            }
+            _ => ending,
+        };
+        self.lines
+            .insert(index, Line::new(self.section, text, &ending));
+    }
+
+    pub fn remove_line(&mut self, index: usize) -> Line {
+        self.lines.remove(index)
+    }
+}
+
+impl fmt::Display for SectionNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(header) = &self.header {
+            write!(f, "{}", header)?;
+        }
+        self.lines.iter().try_for_each(|t| write!(f, "{}", t))
+    }
+}
+
+/// A lossless concrete syntax tree of a `.osu` document, keeping comments, unknown keys and
+/// sections, key order and whitespace. Writing it out reproduces the parsed text exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    sections: Vec<SectionNode>,
+}
+
+impl SyntaxTree {
+    pub fn parse(text: &str) -> Self {
+        let mut sections = vec![SectionNode {
+            section: FileSections::None,
+            header: None,
+            lines: Vec::new(),
+        }];
+
+        for chunk in text.split_inclusive('\n') {
+            let (line, ending) = match chunk.strip_suffix("\r\n") {
+                Some(line) => (line, "\r\n"),
+                None => match chunk.strip_suffix('\n') {
+                    Some(line) => (line, "\n"),
+                    None => (chunk, ""),
+                },
+            };
+
+            if FileSections::is_header(line) {
+                let section = FileSections::get_section(line);
+                sections.push(SectionNode {
+                    section,
+                    header: Some(Line::new(section, line, ending)),
+                    lines: Vec::new(),
+                });
+            } else {
+                let node = sections.last_mut().unwrap();
+                node.lines.push(Line::new(node.section, line, ending));
+            }
+        }
+
+        Self { sections }
+    }
+
+    pub fn sections(&self) -> &[SectionNode] {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut [SectionNode] {
+        &mut self.sections
+    }
+
+    pub fn section(&self, section: FileSections) -> Option<&SectionNode> {
+        self.sections.iter().find(|t| t.section == section)
+    }
+
+    pub fn section_mut(&mut self, section: FileSections) -> Option<&mut SectionNode> {
+        self.sections.iter_mut().find(|t| t.section == section)
+    }
+
+    pub fn get_value(&self, section: FileSections, key: &str) -> Option<&str> {
+        self.section(section)?.find_key(key)?.value()
+    }
+
+    /// Sets the value of `key` in place, or appends a `Key: value` line to the section if the
+    /// key isn't there yet. Returns `false` if the document has no such section.
+    pub fn set_value(&mut self, section: FileSections, key: &str, value: &str) -> bool {
+        let Some(node) = self.section_mut(section) else {
+            return false;
+        };
+
+        match node.find_key_mut(key) {
+            Some(line) => line.set_value(value),
+            None => {
+                let separator = match section {
+                    FileSections::Variables => "=",
+                    FileSections::Metadata | FileSections::Difficulty => ":",
+                    FileSections::Colours => " : ",
+                    _ => ": ",
+                };
+                node.push_line(&format!("{}{}{}", key, separator, value));
+                true
+            }
+        }
+    }
+
+    /// Parses the typed view of the document, straight from the lines of the tree.
+    pub fn to_beatmap(&self) -> Result<Beatmap, ParseError> {
+        let lines = self
+            .sections
+            .iter()
+            .flat_map(|t| t.header.iter().chain(&t.lines))
+            .map(Line::text);
+
+        let mut beatmap = Beatmap::default();
+        fold_items(lines, &ParseOptions::default(), |item, line| {
+            apply_item(item, line, &mut beatmap)
+        })?;
+        Ok(beatmap)
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.sections.iter().try_for_each(|t| write!(f, "{}", t))
+    }
+}
+
+fn tokenize(section: FileSections, text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let trimmed = text.trim_start();
+
+    if trimmed.is_empty() {
+        push_token(&mut tokens, TokenKind::Whitespace, 0..text.len());
+    } else if trimmed.starts_with("//") {
+        let start = text.len() - trimmed.len();
+        push_token(&mut tokens, TokenKind::Whitespace, 0..start);
+        push_token(&mut tokens, TokenKind::Comment, start..text.len());
+    } else if FileSections::is_header(text) {
+        push_trimmed(&mut tokens, TokenKind::Header, text, 0);
+    } else {
+        match section {
+            FileSections::General
+            | FileSections::Editor
+            | FileSections::Metadata
+            | FileSections::Difficulty
+            | FileSections::Colours => tokenize_key_value(&mut tokens, text, ':'),
+            FileSections::Variables => tokenize_key_value(&mut tokens, text, '='),
+            FileSections::Events | FileSections::TimingPoints | FileSections::HitObjects => {
+                tokenize_fields(&mut tokens, text)
+            }
+            _ => push_token(&mut tokens, TokenKind::Text, 0..text.len()),
+        }
+    }
+
+    tokens
+}
+
+fn tokenize_key_value(tokens: &mut Vec<Token>, text: &str, separator: char) {
+    let Some(index) = text.find(separator) else {
+        push_token(tokens, TokenKind::Text, 0..text.len());
+        return;
+    };
+
+    push_trimmed(tokens, TokenKind::Key, &text[..index], 0);
+    push_token(tokens, TokenKind::Separator, index..index + 1);
+
+    // an empty value still gets a token, so it can be filled in later
+    let value = &text[index + 1..];
+    let start = index + 1 + (value.len() - value.trim_start().len());
+    let end = index + 1 + value.trim_end().len();
+    push_token(tokens, TokenKind::Whitespace, index + 1..start);
+    tokens.push(Token {
+        kind: TokenKind::Value,
+        span: start..end.max(start),
+    });
+    push_token(tokens, TokenKind::Whitespace, end.max(start)..text.len());
+}
+
+fn tokenize_fields(tokens: &mut Vec<Token>, text: &str) {
+    let indent = text.len() - text.trim_start_matches([' ', '_']).len();
+    push_token(tokens, TokenKind::Indent, 0..indent);
+
+    let mut in_quotes = false;
+    let mut start = indent;
+    for (index, c) in text.char_indices().skip_while(|(i, _)| *i < indent) {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                tokens.push(Token {
+                    kind: TokenKind::Field,
+                    span: start..index,
+                });
+                push_token(tokens, TokenKind::Comma, index..index + 1);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    tokens.push(Token {
+        kind: TokenKind::Field,
+        span: start..text.len(),
+    });
+}
+
+/// Pushes `part` (found at `offset` in the line) as a token, with its surrounding whitespace
+/// as separate tokens.
+fn push_trimmed(tokens: &mut Vec<Token>, kind: TokenKind, part: &str, offset: usize) {
+    let start = offset + (part.len() - part.trim_start().len());
+    let end = offset + part.trim_end().len();
+    push_token(tokens, TokenKind::Whitespace, offset..start);
+    push_token(tokens, kind, start..end);
+    push_token(tokens, TokenKind::Whitespace, end..offset + part.len());
+}
+
+fn push_token(tokens: &mut Vec<Token>, kind: TokenKind, span: Range<usize>) {
+    if !span.is_empty() {
+        tokens.push(Token { kind, span });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_STR: &str = "osu file format v14\r
+\r
+[General]\r
+AudioFilename: audio.mp3\r
+// a comment the editor must keep\r
+LazerOnlyKey:  1  \r
+\r
+[Metadata]\r
+Title:End Time\r
+Source:\r
+\r
+[Garbage]\r
+whatever, goes\r
+\r
+[HitObjects]\r
+256,192,11000,21,2";
+
+    #[test]
+    fn test_lossless() {
+        let tree = SyntaxTree::parse(TEST_STR);
+
+        assert_eq!(tree.to_string(), TEST_STR);
+        assert_eq!(tree.sections().len(), 6);
+        assert_eq!(
+            tree.get_value(FileSections::General, "LazerOnlyKey"),
+            Some("1")
+        );
+        assert_eq!(
+            tree.section(FileSections::HitObjects).unwrap().lines()[0].fields(),
+            vec!["256", "192", "11000", "21", "2"]
+        );
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut tree = SyntaxTree::parse(TEST_STR);
+
+        assert!(tree.set_value(FileSections::Metadata, "Title", "Start Time"));
+        assert!(tree.set_value(FileSections::Metadata, "Source", "osu!"));
+        assert!(tree.set_value(FileSections::Metadata, "Creator", "PaRaDogi"));
+        assert!(!tree.set_value(FileSections::Difficulty, "CircleSize", "4"));
+        tree.section_mut(FileSections::HitObjects)
+            .unwrap()
+            .lines_mut()[0]
+            .set_field(2, "12000");
+
+        assert_eq!(
+            tree.to_string(),
+            TEST_STR
+                .replace("Title:End Time", "Title:Start Time")
+                .replace("Source:\r\n", "Source:osu!\r\nCreator:PaRaDogi\r\n")
+                .replace("11000", "12000")
+        );
+
+        let beatmap = tree.to_beatmap().unwrap();
+        assert_eq!(beatmap.metadata.title, String::from("Start Time"));
+        assert_eq!(beatmap.metadata.creator, String::from("PaRaDogi"));
+    }
+
+    #[test]
+    fn test_to_beatmap_line_numbers() {
+        let text = TEST_STR.replace("11000", "eleven");
+        let tree = SyntaxTree::parse(&text);
+
+        let error = tree.to_beatmap().unwrap_err();
+        assert_eq!(error.line, 16);
+        assert_eq!(Some(error), crate::parse(&text).err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    fmt,
+    num::ParseFloatError,
+    ops::{Add, AddAssign, Mul, Sub, SubAssign},
+    str::FromStr,
+};
+
+/// A timestamp or a duration, in milliseconds.
+///
+/// The game reads times as doubles, so values may be negative (before the start of the audio)
+/// or fractional. Timing point and storyboard times are kept exactly as written, while hit object
+/// and event times are rounded to whole milliseconds like the game stores them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Time(f64);
+
+impl Time {
+    pub const ZERO: Self = Self(0.0);
+
+    pub const fn from_ms(ms: f64) -> Self {
+        Self(ms)
+    }
+
+    pub const fn as_ms(self) -> f64 {
+        self.0
+    }
+
+    /// The later of two times. `Time` isn't `Ord`, since it holds a double.
+    pub fn max(self, other: Self) -> Self {
+        Self(self.0.max(other.0))
+    }
+
+    /// Rounds to whole milliseconds the way the game does when it stores a time as an integer:
+    /// to the nearest, with halves going to the even neighbour.
+    pub fn round(self) -> i32 {
+        self.0.round_ties_even() as i32
+    }
+}
+
+impl From<i32> for Time {
+    fn from(ms: i32) -> Self {
+        Self(f64::from(ms))
+    }
+}
+
+impl FromStr for Time {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse::<f64>().map(Self)
+    }
+}
+
+/// Writes the shortest decimal that reads back as the same time, so whole milliseconds have no
+/// fractional part.
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Add for Time {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Time {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Time {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl SubAssign for Time {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 -= rhs.0;
+    }
+}
+
+impl Mul<f64> for Time {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!("-24".parse::<Time>(), Ok(Time::from(-24)));
+        assert_eq!(
+            "333.333333333333".parse::<Time>(),
+            Ok(Time::from_ms(333.333333333333))
+        );
+        assert_eq!(Time::from_ms(1500.5).to_string(), "1500.5");
+        assert_eq!(Time::from(1500).to_string(), "1500");
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(Time::from_ms(0.5).round(), 0);
+        assert_eq!(Time::from_ms(1.5).round(), 2);
+        assert_eq!(Time::from_ms(2.5).round(), 2);
+        assert_eq!(Time::from_ms(-2.5).round(), -2);
+        assert_eq!(Time::from_ms(2.6).round(), 3);
+    }
+}
@@ -1,33 +1,56 @@
-use crate::{general::SampleSet, Beatmap};
-use std::str::FromStr;
-use strum::ParseError::VariantNotFound;
+use crate::{
+    error::{parse_column, ParseError, ParseErrorKind},
+    file_sections::FileSections,
+    format::Format,
+    general::SampleSet,
+    time::Time,
+    Beatmap,
+};
+use bitflags::bitflags;
+use std::{fmt, num::ParseIntError, str::FromStr};
 
-#[derive(Debug, PartialEq)]
-pub enum Effects {
-    None,
-    Kiai,
-    OmitFirstBarline,
-    All,
+bitflags! {
+    /// Bits of the `effects` column. Bits the game doesn't use are kept as written.
+    #[derive(Debug, Default, Clone, Copy, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Effects: u8 {
+        const KIAI = 1;
+        const OMIT_FIRST_BARLINE = 1 << 3;
+    }
+}
+
+impl Effects {
+    pub fn is_kiai(&self) -> bool {
+        self.contains(Self::KIAI)
+    }
+
+    /// Whether the first barline is hidden in taiko and mania.
+    pub fn omits_first_barline(&self) -> bool {
+        self.contains(Self::OMIT_FIRST_BARLINE)
+    }
 }
 
 impl FromStr for Effects {
-    type Err = strum::ParseError;
-
-    fn from_str(val: &str) -> Result<Self, Self::Err> {
-        match val {
-            "0" => Ok(Self::None),
-            "1" => Ok(Self::Kiai),
-            "8" => Ok(Self::OmitFirstBarline),
-            "9" => Ok(Self::All),
-            _ => Err(VariantNotFound),
-        }
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse::<u8>().map(Self::from_bits_retain)
+    }
+}
+
+impl fmt::Display for Effects {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.bits())
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TimingPoint {
-    pub time: u32,
-    pub beat_length: f32,
+    pub time: Time,
+    /// Milliseconds per beat for uninherited points, or a negative inverse slider velocity
+    /// percentage for inherited ones. Kept as a double, like the game does.
+    pub beat_length: f64,
     pub meter: u32,
     pub sample_set: SampleSet,
     pub sample_index: u32,
@@ -39,8 +62,8 @@ pub struct TimingPoint {
 #[allow(clippy::too_many_arguments)]
 impl TimingPoint {
     pub fn new(
-        time: u32,
-        beat_length: f32,
+        time: Time,
+        beat_length: f64,
         meter: u32,
         sample_set: SampleSet,
         sample_index: u32,
@@ -63,25 +86,210 @@ impl TimingPoint {
 
 impl Default for TimingPoint {
     fn default() -> Self {
-        TimingPoint::new(0, 500.0, 4, SampleSet::Default, 0, 100, true, Effects::None)
+        TimingPoint::new(
+            Time::ZERO,
+            500.0,
+            4,
+            SampleSet::Default,
+            0,
+            100,
+            true,
+            Effects::empty(),
+        )
+    }
+}
+
+impl fmt::Display for TimingPoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{},{},{},{},{}",
+            self.time,
+            self.beat_length,
+            self.meter,
+            self.sample_set as u8,
+            self.sample_index,
+            self.volume,
+            u8::from(self.uninherited),
+            self.effects
+        )
     }
 }
 
-pub fn parse_timing_points(line: &str, beatmap: &mut Beatmap) {
-    let params: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+/// The timing in effect at some time of a beatmap.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimingState {
+    /// Milliseconds per beat, from the uninherited point.
+    pub beat_length: f64,
+    pub meter: u32,
+    /// The multiplier of `[Difficulty]`'s slider multiplier, from the inherited point.
+    pub slider_velocity: f64,
+    pub sample_set: SampleSet,
+    pub sample_index: u32,
+    pub volume: u32,
+    pub kiai: bool,
+}
+
+impl TimingState {
+    pub fn bpm(&self) -> f64 {
+        60000.0 / self.beat_length
+    }
+}
+
+/// Looks up the timing of a beatmap at any time.
+///
+/// Uninherited (red) points set the beat length and meter, and inherited (green) points the
+/// slider velocity. Sample settings and kiai come from the last point of either kind. Times before
+/// the first point use the first one, like the game does.
+#[derive(Debug, Clone)]
+pub struct TimingModel<'a> {
+    timing_points: Vec<&'a TimingPoint>,
+    uninherited: Vec<&'a TimingPoint>,
+    inherited: Vec<&'a TimingPoint>,
+}
+
+impl<'a> TimingModel<'a> {
+    /// The sort is stable, so points at the same time keep their file order and the last one
+    /// written decides the sample settings and kiai.
+    pub fn new(timing_points: &'a [TimingPoint]) -> Self {
+        let mut timing_points: Vec<&TimingPoint> = timing_points.iter().collect();
+        timing_points.sort_by(|a, b| a.time.as_ms().total_cmp(&b.time.as_ms()));
+        let (uninherited, inherited) = timing_points.iter().partition(|t| t.uninherited);
+
+        Self {
+            timing_points,
+            uninherited,
+            inherited,
+        }
+    }
+
+    pub fn uninherited(&self) -> &[&'a TimingPoint] {
+        &self.uninherited
+    }
+
+    pub fn inherited(&self) -> &[&'a TimingPoint] {
+        &self.inherited
+    }
+
+    /// The last point of either kind at or before `time`.
+    pub fn timing_point_at(&self, time: Time) -> Option<&'a TimingPoint> {
+        point_at(&self.timing_points, time)
+    }
+
+    /// The uninherited point in effect at `time`.
+    pub fn uninherited_point_at(&self, time: Time) -> Option<&'a TimingPoint> {
+        point_at(&self.uninherited, time)
+    }
+
+    pub fn beat_length_at(&self, time: Time) -> f64 {
+        self.uninherited_point_at(time)
+            .map_or(TimingPoint::default().beat_length, |t| t.beat_length)
+    }
+
+    pub fn bpm_at(&self, time: Time) -> f64 {
+        60000.0 / self.beat_length_at(time)
+    }
+
+    pub fn meter_at(&self, time: Time) -> u32 {
+        self.uninherited_point_at(time)
+            .map_or(TimingPoint::default().meter, |t| t.meter)
+    }
+
+    /// Set by the last inherited point and reset to 1x by uninherited ones. The game clamps it
+    /// between 0.1x and 10x.
+    pub fn slider_velocity_at(&self, time: Time) -> f64 {
+        let before = |points: &[&'a TimingPoint]| {
+            points[..points.partition_point(|t| t.time <= time)]
+                .last()
+                .copied()
+        };
+
+        match (before(&self.inherited), before(&self.uninherited)) {
+            (Some(t), Some(reset)) if t.time < reset.time => 1.0,
+            (Some(t), _) if t.beat_length < 0.0 => (-100.0 / t.beat_length).clamp(0.1, 10.0),
+            _ => 1.0,
+        }
+    }
+
+    pub fn at(&self, time: Time) -> TimingState {
+        let default_timing_point = TimingPoint::default();
+        let timing_point = self.timing_point_at(time).unwrap_or(&default_timing_point);
+
+        TimingState {
+            beat_length: self.beat_length_at(time),
+            meter: self.meter_at(time),
+            slider_velocity: self.slider_velocity_at(time),
+            sample_set: timing_point.sample_set,
+            sample_index: timing_point.sample_index,
+            volume: timing_point.volume,
+            kiai: timing_point.effects.is_kiai(),
+        }
+    }
+}
 
-    let timing_point = TimingPoint {
-        time: params[0].parse::<u32>().unwrap(),
-        beat_length: params[1].parse::<f32>().unwrap(),
-        meter: params[2].parse::<u32>().unwrap(),
-        sample_set: SampleSet::from_str(params[3]).unwrap(),
-        sample_index: params[4].parse::<u32>().unwrap(),
-        volume: params[5].parse::<u32>().unwrap(),
-        uninherited: params[6].parse::<u8>().unwrap() != 0,
-        effects: Effects::from_str(params[7]).unwrap(),
+/// The last point at or before `time`, or the first one for times before all of them.
+fn point_at<'a>(timing_points: &[&'a TimingPoint], time: Time) -> Option<&'a TimingPoint> {
+    let index = timing_points.partition_point(|t| t.time <= time);
+    timing_points[..index]
+        .last()
+        .or(timing_points.first())
+        .copied()
+}
+
+pub fn parse_timing_points(line: &str, beatmap: &mut Beatmap) -> Result<(), ParseError> {
+    let timing_point = parse_timing_point(line, &beatmap.format)?;
+    beatmap.timing_points.push(timing_point);
+
+    Ok(())
+}
+
+/// Parses a timing point. Files before v14 may end the line after any column past the beat
+/// length, in which case the remaining columns take the values the game assumes for them.
+pub fn parse_timing_point(line: &str, format: &Format) -> Result<TimingPoint, ParseError> {
+    let params: Vec<&str> = line.split(',').collect();
+    let column =
+        |index| move |kind| ParseError::column(FileSections::TimingPoints, line, index, kind);
+    let present = |index: usize| match (params.len() > index, format.version) {
+        (true, _) => Ok(true),
+        (false, 14..) => Err(column(index)(ParseErrorKind::MissingField)),
+        (false, _) => Ok(false),
+    };
+
+    let time = parse_column::<Time>(&params, 0).map_err(column(0))?;
+    let beat_length = parse_column::<f64>(&params, 1).map_err(column(1))?;
+    let mut timing_point = TimingPoint {
+        time: time + format.offset(),
+        beat_length,
+        // before inherited points had their own column, a negative beat length marked them
+        uninherited: beat_length >= 0.0,
+        ..TimingPoint::default()
     };
 
-    beatmap.timing_points.push(timing_point)
+    if present(2)? {
+        // a meter of 0 is written by some old editors and means the default
+        timing_point.meter = match parse_column::<u32>(&params, 2).map_err(column(2))? {
+            0 => 4,
+            t => t,
+        };
+    }
+    if present(3)? {
+        timing_point.sample_set = parse_column(&params, 3).map_err(column(3))?;
+    }
+    if present(4)? {
+        timing_point.sample_index = parse_column(&params, 4).map_err(column(4))?;
+    }
+    if present(5)? {
+        timing_point.volume = parse_column(&params, 5).map_err(column(5))?;
+    }
+    if present(6)? {
+        timing_point.uninherited = parse_column::<u8>(&params, 6).map_err(column(6))? != 0;
+    }
+    if present(7)? {
+        timing_point.effects = parse_column(&params, 7).map_err(column(7))?;
+    }
+
+    Ok(timing_point)
 }
 
 #[cfg(test)]
@@ -92,21 +300,170 @@ mod tests {
     fn test_parse_timing_points() {
         let test_str = "1342, 333.33, 4, 2, 1, 35, 1, 0";
         let mut beatmap = Beatmap::default();
-        parse_timing_points(test_str, &mut beatmap);
+        parse_timing_points(test_str, &mut beatmap).unwrap();
 
         assert_eq!(beatmap.timing_points.len(), 1);
         assert_eq!(
             beatmap.timing_points[0],
             TimingPoint {
-                time: 1342,
+                time: Time::from(1342),
                 beat_length: 333.33,
                 meter: 4,
                 sample_set: SampleSet::Soft,
                 sample_index: 1,
                 volume: 35,
                 uninherited: true,
-                effects: Effects::None,
+                effects: Effects::empty(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_timing_points_precision() {
+        let test_str = "-28.5,333.333333333333,4,2,1,35,1,0";
+        let mut beatmap = Beatmap::default();
+        parse_timing_points(test_str, &mut beatmap).unwrap();
+
+        assert_eq!(beatmap.timing_points[0].time, Time::from_ms(-28.5));
+        assert_eq!(beatmap.timing_points[0].beat_length, 333.333333333333);
+        assert_eq!(beatmap.timing_points[0].to_string(), test_str);
+    }
+
+    #[test]
+    fn test_parse_effects() {
+        let effects = "9".parse::<Effects>().unwrap();
+        assert!(effects.is_kiai());
+        assert!(effects.omits_first_barline());
+
+        let effects = "5".parse::<Effects>().unwrap();
+        assert!(effects.is_kiai());
+        assert!(!effects.omits_first_barline());
+        assert_eq!(effects.to_string(), "5");
+    }
+
+    #[test]
+    fn test_parse_timing_points_missing_field() {
+        let test_str = "1342, 333.33, 4, 2";
+        let mut beatmap = Beatmap::default();
+
+        assert_eq!(
+            parse_timing_points(test_str, &mut beatmap),
+            Err(ParseError::column(
+                FileSections::TimingPoints,
+                test_str,
+                4,
+                ParseErrorKind::MissingField
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_timing_points() {
+        let test_str = "1342,333.33
+            2000,-50,3,1
+            3000,500,0,2,0,60
+            4000,-100,4,2,1,70";
+        let mut beatmap = Beatmap {
+            format: Format::new(4),
+            ..Beatmap::default()
+        };
+        for line in test_str.lines() {
+            parse_timing_points(line, &mut beatmap).unwrap();
+        }
+
+        assert_eq!(
+            beatmap.timing_points,
+            vec![
+                TimingPoint::new(
+                    Time::from(1366),
+                    333.33,
+                    4,
+                    SampleSet::Default,
+                    0,
+                    100,
+                    true,
+                    Effects::empty()
+                ),
+                TimingPoint::new(
+                    Time::from(2024),
+                    -50.0,
+                    3,
+                    SampleSet::Normal,
+                    0,
+                    100,
+                    false,
+                    Effects::empty()
+                ),
+                TimingPoint::new(
+                    Time::from(3024),
+                    500.0,
+                    4,
+                    SampleSet::Soft,
+                    0,
+                    60,
+                    true,
+                    Effects::empty()
+                ),
+                TimingPoint::new(
+                    Time::from(4024),
+                    -100.0,
+                    4,
+                    SampleSet::Soft,
+                    1,
+                    70,
+                    false,
+                    Effects::empty()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timing_model() {
+        let timing_points: Vec<TimingPoint> = [
+            "1000,500,4,2,1,60,1,0",
+            "2000,-50,4,2,2,70,0,1",
+            "3000,400,3,1,0,80,1,0",
+            "4000,-5,4,1,0,80,0,0",
+            "4000,-200,4,1,0,90,0,0",
+            "3000,-200,4,3,0,80,0,0",
+        ]
+        .iter()
+        .map(|t| parse_timing_point(t, &Format::default()).unwrap())
+        .collect();
+        let timing = TimingModel::new(&timing_points);
+
+        assert_eq!(timing.uninherited().len(), 2);
+        assert_eq!(timing.inherited().len(), 4);
+        assert_eq!(
+            timing.at(Time::from(0)),
+            TimingState {
+                beat_length: 500.0,
+                meter: 4,
+                slider_velocity: 1.0,
+                sample_set: SampleSet::Soft,
+                sample_index: 1,
+                volume: 60,
+                kiai: false,
             }
         );
+        assert_eq!(timing.bpm_at(Time::from(1500)), 120.0);
+
+        let state = timing.at(Time::from(2500));
+        assert_eq!(state.slider_velocity, 2.0);
+        assert_eq!(
+            (state.sample_index, state.volume, state.kiai),
+            (2, 70, true)
+        );
+
+        // the inherited point written after the uninherited one at 3000 still applies
+        let state = timing.at(Time::from(3000));
+        assert_eq!(state.bpm(), 150.0);
+        assert_eq!(state.meter, 3);
+        assert_eq!(state.slider_velocity, 0.5);
+        assert_eq!(state.sample_set, SampleSet::Drum);
+
+        assert_eq!(timing.slider_velocity_at(Time::from(4000)), 0.5);
+        assert_eq!(timing.at(Time::from(4000)).volume, 90);
     }
 }
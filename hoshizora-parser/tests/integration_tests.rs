@@ -1,17 +1,118 @@
-use hoshizora_parser::parse;
+#[cfg(feature = "rayon")]
+use hoshizora_parser::parse_songs_directory;
+#[cfg(feature = "serde")]
+use hoshizora_parser::Beatmap;
+use hoshizora_parser::{
+    events::{Background, Event},
+    parse, parse_file,
+    syntax::SyntaxTree,
+    FileSections,
+};
 use std::{
     fs::File,
     io::{prelude::*, BufReader},
 };
 
-#[test]
-fn test_parse_from_file() {
-    let file = File::open("./tests/end_time.osu").unwrap();
+fn read_test_file(path: &str) -> String {
+    let file = File::open(path).unwrap();
     let mut buf_reader = BufReader::new(file);
     let mut content = String::new();
     buf_reader.read_to_string(&mut content).unwrap();
+    content
+}
 
-    let beatmap = parse(&content);
+#[test]
+fn test_parse_from_file() {
+    let content = read_test_file("./tests/end_time.osu");
+
+    let beatmap = parse(&content).unwrap();
 
     assert_eq!(beatmap.metadata.title, String::from("End Time"));
+    assert_eq!(
+        beatmap.events,
+        vec![Event::Background(Background {
+            filename: String::from("endtime.jpg"),
+            x_offset: 0,
+            y_offset: 0,
+        })]
+    );
+}
+
+#[test]
+fn test_write_round_trip() {
+    let content = read_test_file("./tests/end_time.osu");
+    let beatmap = parse(&content).unwrap();
+
+    let mut written = Vec::new();
+    beatmap.write_to(&mut written).unwrap();
+    let written = String::from_utf8(written).unwrap();
+
+    assert!(written.starts_with("osu file format v14\n"));
+    assert_eq!(parse(&written).unwrap(), beatmap);
+}
+
+#[test]
+fn test_syntax_tree_round_trip() {
+    let content = read_test_file("./tests/end_time.osu");
+    let mut tree = SyntaxTree::parse(&content);
+
+    assert_eq!(tree.to_string(), content);
+    assert_eq!(tree.to_beatmap().unwrap(), parse(&content).unwrap());
+
+    tree.set_value(FileSections::Difficulty, "ApproachRate", "9.3");
+    let edited = tree.to_string();
+    assert_eq!(
+        edited,
+        content.replace("ApproachRate:9.6", "ApproachRate:9.3")
+    );
+    assert_eq!(tree.to_beatmap().unwrap().difficulty.ar, 9.3);
+}
+
+#[test]
+fn test_parse_file() {
+    let beatmap = parse_file("./tests/end_time.osu").unwrap();
+
+    assert_eq!(
+        beatmap,
+        parse(&read_test_file("./tests/end_time.osu")).unwrap()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    let beatmap = parse(&read_test_file("./tests/end_time.osu")).unwrap();
+    let json = serde_json::to_string(&beatmap).unwrap();
+
+    assert!(json.contains("\"audio_filename\":\"audio.mp3\""));
+    assert_eq!(serde_json::from_str::<Beatmap>(&json).unwrap(), beatmap);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parse_songs_directory() {
+    let parsed = parse_songs_directory("./tests").unwrap();
+
+    assert_eq!(parsed.len(), 1);
+    assert!(parsed[0].path.ends_with("end_time.osu"));
+    assert_eq!(
+        parsed[0].result.as_ref().unwrap().metadata.title,
+        String::from("End Time")
+    );
+}
+
+#[cfg(all(feature = "rayon", unix))]
+#[test]
+fn test_parse_songs_directory_symlink_loop() {
+    let root = std::env::temp_dir().join("hoshizora-symlink-loop");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("set")).unwrap();
+    std::fs::copy("./tests/end_time.osu", root.join("set/end_time.osu")).unwrap();
+    std::os::unix::fs::symlink(&root, root.join("set/loop")).unwrap();
+
+    let parsed = parse_songs_directory(&root).unwrap();
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(parsed.len(), 1);
+    assert!(parsed[0].result.is_ok());
 }